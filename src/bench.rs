@@ -0,0 +1,158 @@
+use std::time::Instant;
+
+use color_eyre::eyre::Result;
+use rand::{Rng, SeedableRng, rngs::StdRng};
+
+use crate::game::{OuterBoard, Variant, searcher};
+
+const POSITIONS: usize = 2000;
+const SEARCH_DEPTH: usize = 4;
+const CALIBRATION_GAMES: usize = 40;
+const CALIBRATION_RANDOM_PLIES: usize = 4;
+
+// the default time limit, stopping at `max_depth`
+fn to_depth(max_depth: usize) -> searcher::SearchConfig {
+    searcher::SearchConfig {
+        max_depth,
+        ..Default::default()
+    }
+}
+
+fn positions() -> Vec<(OuterBoard, crate::game::Mark)> {
+    let mut rng = StdRng::seed_from_u64(0);
+    (0..POSITIONS)
+        .map(|_| {
+            let plies = rng.random_range(0..40);
+            OuterBoard::random_playout(&mut rng, plies)
+        })
+        .collect()
+}
+
+// how well `win_probability` predicts self-play: the log loss of every searched eval against
+// how its game ended, at the chosen scale and around it
+fn calibrate_win_probability() -> Result<()> {
+    use crate::game::Mark;
+    let mut rng = StdRng::seed_from_u64(6);
+    // (eval from X's side, X's result)
+    let mut samples = vec![];
+    for _ in 0..CALIBRATION_GAMES {
+        let mut board = OuterBoard::default();
+        let mut to_move = Mark::X;
+        let mut evals = vec![];
+        for ply in 0.. {
+            if board.is_over() {
+                break;
+            }
+            // a few random openings so the games don't all come out the same
+            let r#move = if ply < CALIBRATION_RANDOM_PLIES {
+                let moves = board.possible_moves(to_move);
+                moves[rng.random_range(0..moves.len())]
+            } else {
+                let (best, _) =
+                    searcher::Searcher::search(&board, to_move, to_depth(2), Default::default());
+                let (r#move, eval) = best.unwrap();
+                evals.push(if to_move == Mark::X {
+                    eval
+                } else {
+                    eval.saturating_neg()
+                });
+                r#move
+            };
+            board = board.try_move(r#move)?;
+            to_move = !to_move;
+        }
+        let result = match board.overall_winner {
+            Some(Mark::X) => 1.0,
+            Some(Mark::O) => 0.0,
+            None => 0.5,
+        };
+        samples.extend(evals.into_iter().map(|eval| (eval, result)));
+    }
+    let loss = |scale: f64| {
+        samples
+            .iter()
+            .map(|&(eval, result)| {
+                let p = crate::win_probability_at(eval, scale).clamp(1e-6, 1.0 - 1e-6);
+                -(result * p.ln() + (1.0 - result) * (1.0 - p).ln())
+            })
+            .sum::<f64>()
+            / samples.len() as f64
+    };
+    let report: Vec<_> = [0.5, 0.75, 1.0, 1.5, 2.0]
+        .iter()
+        .map(|factor| {
+            let scale = crate::EVAL_SCALE * factor;
+            format!("{scale}: {:.3}", loss(scale))
+        })
+        .collect();
+    println!(
+        "win probability calibration, log loss over {} evals at each scale: {}",
+        samples.len(),
+        report.join(", ")
+    );
+    Ok(())
+}
+
+pub fn run() -> Result<()> {
+    calibrate_win_probability()?;
+
+    let positions = positions();
+    let start = Instant::now();
+    let mut checksum = 0i64;
+    for &(board, to_move) in &positions {
+        checksum += searcher::Searcher::heuristic(
+            &board,
+            to_move,
+            to_move,
+            Variant::Standard,
+            &Default::default(),
+            0,
+        ) as i64;
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "heuristic: {:.0} ns/call (checksum {checksum})",
+        elapsed.as_nanos() as f64 / positions.len() as f64
+    );
+
+    let start = Instant::now();
+    let mut children = 0usize;
+    for &(board, to_move) in &positions {
+        for r#move in board.possible_moves(to_move) {
+            children += board.make_move(r#move).is_some() as usize;
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "make_move: {:.0} ns/call over {children} moves",
+        elapsed.as_nanos() as f64 / children as f64
+    );
+
+    let start = Instant::now();
+    let mut generated = 0usize;
+    for &(board, to_move) in &positions {
+        generated += board.moves(to_move).count();
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "moves: {:.1} ns/move over {generated} moves",
+        elapsed.as_nanos() as f64 / generated as f64
+    );
+
+    let (board, to_move) = positions[positions.len() / 2];
+    searcher::clear_transposition_table();
+    let (_, stats) = searcher::Searcher::search_deterministic(
+        &board,
+        to_move,
+        SEARCH_DEPTH,
+        searcher::SearchOptions::default(),
+    );
+    println!(
+        "search to depth {SEARCH_DEPTH}: {} nodes in {} ms ({:.0} nodes/s)",
+        stats.nodes,
+        stats.elapsed.as_millis(),
+        stats.nodes as f64 / stats.elapsed.as_secs_f64()
+    );
+
+    Ok(())
+}
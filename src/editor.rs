@@ -0,0 +1,115 @@
+use eframe::egui;
+
+use crate::{
+    GridHelper, draw_grid_item, draw_grid_lines,
+    game::{Mark, OuterBoard},
+    settings::BoardStyle,
+};
+
+pub enum EditorAction {
+    Start(OuterBoard),
+    Cancel,
+}
+
+pub struct Editor {
+    board: OuterBoard,
+}
+
+impl Editor {
+    pub fn new(board: OuterBoard) -> Self {
+        Self { board }
+    }
+
+    pub fn show_controls(&mut self, ui: &mut egui::Ui) -> Option<EditorAction> {
+        ui.label("Clicca una casella per cambiarla: vuota → X → O");
+        if ui.button("Svuota").clicked() {
+            self.board = OuterBoard {
+                ruleset: self.board.ruleset,
+                ..Default::default()
+            };
+        }
+
+        let describe = |square: Option<(u8, u8)>| {
+            square.map_or("Nessuna (scelta libera)".to_string(), |(r, c)| {
+                format!("Riga {} colonna {}", r + 1, c + 1)
+            })
+        };
+        egui::ComboBox::from_label("Sottotabella attiva")
+            .selected_text(describe(self.board.active_square))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.board.active_square, None, describe(None));
+                for r in 0..3 {
+                    for c in 0..3 {
+                        let square = Some((r, c));
+                        ui.selectable_value(
+                            &mut self.board.active_square,
+                            square,
+                            describe(square),
+                        );
+                    }
+                }
+            });
+
+        let validation = self.board.validate();
+        match validation {
+            Ok(()) => {
+                ui.label(format!("Tocca a: {}", self.board.side_to_move()));
+            }
+            Err(e) => {
+                ui.colored_label(egui::Color32::RED, format!("Posizione non valida: {e}"));
+            }
+        }
+
+        let mut action = None;
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(validation.is_ok(), egui::Button::new("Inizia da qui"))
+                .clicked()
+            {
+                action = Some(EditorAction::Start(self.board));
+            }
+            if ui.button("Annulla").clicked() {
+                action = Some(EditorAction::Cancel);
+            }
+        });
+        action
+    }
+
+    pub fn draw(&mut self, ui: &mut egui::Ui, style: &BoardStyle, gh: GridHelper) {
+        draw_grid_lines(ui, style, gh, self.board.active_square.is_none());
+
+        let mut changed = false;
+        for row in 0..3 {
+            for col in 0..3 {
+                let sub_gh = gh.subgrid(row, col);
+                draw_grid_lines(
+                    ui,
+                    style,
+                    sub_gh,
+                    self.board.active_square == Some((row, col)),
+                );
+
+                for inner_row in 0..3 {
+                    for inner_col in 0..3 {
+                        let square = &mut self.board.boards[row as usize][col as usize].squares
+                            [inner_row as usize][inner_col as usize];
+                        let response =
+                            draw_grid_item(ui, style, sub_gh, inner_row, inner_col, *square, false);
+                        if response.clicked() {
+                            *square = match *square {
+                                None => Some(Mark::X),
+                                Some(Mark::X) => Some(Mark::O),
+                                Some(Mark::O) => None,
+                            };
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        if changed {
+            self.board.recompute_winners();
+        }
+    }
+}
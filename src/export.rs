@@ -0,0 +1,220 @@
+use std::io::Read;
+
+use color_eyre::eyre::{Result, eyre};
+
+use crate::{
+    game::{Mark, Move, OuterBoard, Ruleset, Variant, searcher},
+    reproducer,
+};
+
+// shallow enough to answer at once, deep enough to see the tactics around the position
+const SEARCH_DEPTH: usize = 4;
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    InProgress,
+    Won { winner: Mark },
+    Drawn,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct MoveReport {
+    #[serde(rename = "move")]
+    pub r#move: Move,
+    // the same move as `supertris reproduce` and "Incolla partita" write it
+    pub notation: String,
+    pub eval: i32,
+}
+
+// evals are all from the point of view of `to_move`, best move first
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct PositionReport {
+    pub to_move: Mark,
+    pub status: Status,
+    pub eval: i32,
+    pub depth: usize,
+    pub moves: Vec<MoveReport>,
+    pub pv: Vec<String>,
+}
+
+// one ply of "Esporta mosse"
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct TranscriptMove {
+    pub ply: usize,
+    pub player: Mark,
+    // `Move::to_notation`
+    pub notation: String,
+    // from the computer's point of view, as the eval bar shows it
+    pub eval: i32,
+}
+
+// the moves back out of what "Esporta mosse" wrote; whether they can be played is up to the
+// replay
+pub fn read_transcript(json: &str) -> Result<Vec<Move>> {
+    let entries: Vec<TranscriptMove> = serde_json::from_str(json)?;
+    entries
+        .iter()
+        .map(|entry| {
+            Move::from_notation(&entry.notation, entry.player)
+                .map_err(|e| eyre!("mossa {} (\"{}\"): {e}", entry.ply, entry.notation))
+        })
+        .collect()
+}
+
+pub fn transcript(moves: impl IntoIterator<Item = (Move, i32)>) -> Vec<TranscriptMove> {
+    moves
+        .into_iter()
+        .enumerate()
+        .map(|(i, (r#move, eval))| TranscriptMove {
+            ply: i + 1,
+            player: r#move.player,
+            notation: r#move.to_notation(),
+            eval,
+        })
+        .collect()
+}
+
+// every legal move with the value the multi-PV search gives it, plus the line after the best
+pub fn report(
+    board: &OuterBoard,
+    to_move: Mark,
+    options: searcher::SearchOptions,
+) -> PositionReport {
+    let status = match board.overall_winner {
+        Some(line_owner) => Status::Won {
+            winner: options.variant.winner(line_owner),
+        },
+        None if board.is_over() => Status::Drawn,
+        None => Status::InProgress,
+    };
+    searcher::clear_transposition_table();
+    let top = match status {
+        Status::InProgress => {
+            let config = searcher::SearchConfig {
+                max_depth: SEARCH_DEPTH,
+                ..Default::default()
+            };
+            searcher::Searcher::search_multipv(board, to_move, config, options, usize::MAX).0
+        }
+        _ => vec![],
+    };
+    let eval = top.first().map_or_else(
+        || {
+            searcher::Searcher::heuristic(
+                board,
+                to_move,
+                to_move,
+                options.variant,
+                &options.weights,
+                options.draw_score(),
+            )
+        },
+        |&(_, eval)| eval,
+    );
+    let pv = top.first().map_or_else(Vec::new, |&(best, _)| {
+        searcher::Searcher::principal_variation(board, to_move, options, best)
+    });
+    PositionReport {
+        to_move,
+        status,
+        eval,
+        depth: SEARCH_DEPTH,
+        moves: top
+            .into_iter()
+            .map(|(r#move, eval)| MoveReport {
+                r#move,
+                notation: r#move.to_string(),
+                eval,
+            })
+            .collect(),
+        pv: pv.iter().map(Move::to_string).collect(),
+    }
+}
+
+// `supertris export`: a reproducer or a bare list of moves on stdin, the report as JSON on stdout
+pub fn run() -> Result<()> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text)?;
+    let reproducer = reproducer::parse_transcript(&text, Variant::default(), Ruleset::default())?;
+    let board = *reproducer.replay()?.last().unwrap();
+    let options = searcher::SearchOptions {
+        variant: reproducer.variant,
+        ..Default::default()
+    };
+    let report = report(&board, reproducer.to_move(), options);
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    // X holds the top left and top middle subboards and is sent to the top right one, where a
+    // move completes the top row of subboards
+    fn winnable_board() -> OuterBoard {
+        let mut board = OuterBoard::default();
+        for r in 0..3 {
+            board.boards[0][0].squares[r][0] = Some(Mark::X);
+            board.boards[0][1].squares[r][1] = Some(Mark::X);
+        }
+        board.boards[0][2].squares[0][0] = Some(Mark::X);
+        board.boards[0][2].squares[0][1] = Some(Mark::X);
+        board.recompute_winners();
+        board.active_square = Some((0, 2));
+        board
+    }
+
+    // the JSON report lists every legal move, best first, survives being parsed back, and says
+    // when the game is over
+    #[test]
+    fn the_report_lists_every_move_best_first() {
+        let board = winnable_board();
+        let listed = report(&board, Mark::X, Default::default());
+        assert_eq!(listed.moves.len(), board.possible_moves(Mark::X).len());
+        assert_eq!(listed.status, Status::InProgress);
+        assert_eq!(listed.pv.first(), Some(&listed.moves[0].notation));
+        assert_eq!(listed.eval, listed.moves[0].eval);
+        let parsed: PositionReport =
+            serde_json::from_str(&serde_json::to_string(&listed).unwrap()).unwrap();
+        assert_eq!(parsed.moves.len(), listed.moves.len());
+        assert_eq!(parsed.pv, listed.pv);
+
+        let won = listed
+            .moves
+            .iter()
+            .filter_map(|reported| board.make_move(reported.r#move))
+            .find(|child| child.overall_winner.is_some())
+            .expect("the move winning the game is reported");
+        let after = report(&won, Mark::O, Default::default());
+        assert_eq!(after.status, Status::Won { winner: Mark::X });
+        assert!(after.moves.is_empty());
+    }
+
+    // an exported game replays from its notations alone
+    #[test]
+    fn a_transcript_replays() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut moves = vec![];
+        let mut board = OuterBoard::<3>::default();
+        let mut to_move = Mark::X;
+        while moves.len() < 40 && !board.is_over() {
+            let legal = board.possible_moves(to_move);
+            let r#move = legal[rng.random_range(0..legal.len())];
+            board = board.try_move(r#move).unwrap();
+            moves.push((r#move, moves.len() as i32));
+            to_move = !to_move;
+        }
+        let json = serde_json::to_string(&transcript(moves.iter().copied())).unwrap();
+        let parsed = read_transcript(&json).unwrap();
+        assert_eq!(
+            parsed,
+            moves.iter().map(|&(r#move, _)| r#move).collect::<Vec<_>>()
+        );
+        let replayed = OuterBoard::<3>::default().replay(&parsed).unwrap();
+        assert_eq!(replayed.last(), Some(&board));
+    }
+}
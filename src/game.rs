@@ -1,18 +1,32 @@
-use std::{
-    mem::{MaybeUninit, transmute},
-    ops::Not,
-};
+use std::{mem::MaybeUninit, ops::Not, sync::LazyLock};
 
 use arrayvec::ArrayVec;
 
 pub mod searcher;
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, bincode::Encode, bincode::Decode)]
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Hash,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+)]
 pub enum Mark {
     X,
     O,
 }
 
+// `possible_moves` returns fixed-capacity vectors, which bounds the board size
+pub const MAX_SIZE: usize = 4;
+const MAX_CELLS: usize = MAX_SIZE * MAX_SIZE;
+const MAX_MOVES: usize = MAX_CELLS * MAX_CELLS;
+
+// the sides a game gets unless the human picks O, which swaps them for that game
 pub const HUMAN_MARK: Mark = Mark::X;
 pub const COMPUTER_MARK: Mark = Mark::O;
 
@@ -39,60 +53,748 @@ impl std::fmt::Display for Mark {
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Draw;
 
-#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Hash, bincode::Encode, bincode::Decode)]
-pub struct InnerBoard {
-    pub squares: [[Option<Mark>; 3]; 3],
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveError {
+    OutOfBounds,
+    WrongSubboard { expected: (u8, u8) },
+    GameOver,
+    SubboardClosed,
+    CellOccupied,
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::OutOfBounds => write!(f, "coordinate fuori dal tabellone"),
+            MoveError::WrongSubboard { expected } => write!(
+                f,
+                "bisogna giocare nella sottotabella {},{}",
+                expected.0 + 1,
+                expected.1 + 1
+            ),
+            MoveError::GameOver => write!(f, "la partita è finita"),
+            MoveError::SubboardClosed => write!(f, "la sottotabella è chiusa"),
+            MoveError::CellOccupied => write!(f, "la casella è già occupata"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+// the first move of a replayed game that the rules turned down, counting plies from 1
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReplayError {
+    pub ply: usize,
+    pub r#move: Move,
+    pub error: MoveError,
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mossa {} ({}): {}", self.ply, self.r#move, self.error)
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PositionError {
+    UnbalancedMarks { x: usize, o: usize },
+    BothSidesWon { outer: (u8, u8) },
+    ActiveSubboardClosed,
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PositionError::UnbalancedMarks { x, o } => {
+                write!(f, "numero di segni impossibile (X: {x}, O: {o})")
+            }
+            PositionError::BothSidesWon { outer } => write!(
+                f,
+                "la sottotabella {},{} ha un tris per entrambi",
+                outer.0 + 1,
+                outer.1 + 1
+            ),
+            PositionError::ActiveSubboardClosed => write!(f, "la sottotabella attiva è chiusa"),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParseMoveError;
+
+impl std::fmt::Display for ParseMoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mossa non valida, il formato è X11-22")
+    }
+}
+
+impl std::error::Error for ParseMoveError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ParseNotationError;
+
+impl std::fmt::Display for ParseNotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "mossa non valida, il formato è B2/b2")
+    }
+}
+
+impl std::error::Error for ParseNotationError {}
+
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Default,
+    Hash,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum Variant {
+    #[default]
+    Standard,
+    // completing a line of subboards loses
+    Misere,
+}
+
+impl Variant {
+    pub fn winner(self, line_owner: Mark) -> Mark {
+        match self {
+            Variant::Standard => line_owner,
+            Variant::Misere => !line_owner,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct InnerBoard<const N: usize = 3> {
+    pub squares: [[Option<Mark>; N]; N],
     pub winner: Option<Mark>,
+    // `threats` for X and O, kept in step with `squares` by every method that writes them. As
+    // it only depends on `squares` it's left out of what compares or hashes equal, and it
+    // isn't encoded
+    cached_threats: [u8; 2],
+    // the same for how many lines X and O could each still complete
+    cached_open_lines: [u8; 2],
+}
+
+/// Which subboards stay in play, and so where a player sent to a closed board may go.
+#[derive(
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Debug,
+    Default,
+    Hash,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub enum Ruleset {
+    /// Won or full subboards are closed; being sent to one lets you play in any open subboard.
+    #[default]
+    FreeChoice,
+    /// Won subboards stay open until full, though further marks in them change nothing. Only
+    /// being sent to a full subboard lets you play anywhere.
+    WonBoardsStayOpen,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Hash, bincode::Encode, bincode::Decode)]
-pub struct OuterBoard {
-    pub boards: [[InnerBoard; 3]; 3],
+#[derive(Clone, Copy, Debug)]
+pub struct OuterBoard<const N: usize = 3> {
+    pub boards: [[InnerBoard<N>; N]; N],
     pub overall_winner: Option<Mark>,
+    // no line of subboards can be completed by either side any more
+    pub overall_draw: bool,
     pub active_square: Option<(u8, u8)>,
+    pub ruleset: Ruleset,
+    // the Zobrist keys of every mark and subboard winner, and of an odd number of marks, kept
+    // in step by every method that writes those; boards filled in by hand get it back from
+    // `recompute_winners`. The active square and the ruleset are assigned directly all over,
+    // so `zobrist` adds them in when asked instead. Like the caches in `InnerBoard` it's left
+    // out of what compares or hashes equal, so a board filled in by hand is still the same
+    // position before it's recomputed
+    pub hash: u64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, bincode::Encode, bincode::Decode)]
-pub struct Move {
-    pub outer: (u8, u8),
-    pub inner: (u8, u8),
-    pub player: Mark,
+impl<const N: usize> Default for InnerBoard<N> {
+    fn default() -> Self {
+        Self {
+            squares: [[None; N]; N],
+            winner: None,
+            cached_threats: [0; 2],
+            cached_open_lines: [2 * N as u8 + 2; 2],
+        }
+    }
 }
 
-impl InnerBoard {
-    fn update_winner(&mut self) {
-        if self.winner.is_some() {
-            return;
+impl<const N: usize> Default for OuterBoard<N> {
+    fn default() -> Self {
+        Self {
+            boards: [[InnerBoard::default(); N]; N],
+            overall_winner: None,
+            overall_draw: false,
+            active_square: None,
+            ruleset: Ruleset::default(),
+            hash: 0,
         }
+    }
+}
 
-        for row in 0..3 {
-            if let Some(player) = self.squares[row][0] {
-                if self.squares[row][1] == Some(player) && self.squares[row][2] == Some(player) {
-                    self.winner = Some(player);
-                    return;
-                }
+// bincode's derive can't handle the defaulted const parameter, so these mirror what it would
+// generate and keep the save format unchanged
+impl<const N: usize> bincode::Encode for InnerBoard<N> {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        self.squares.encode(encoder)?;
+        self.winner.encode(encoder)
+    }
+}
+
+impl<Context, const N: usize> bincode::Decode<Context> for InnerBoard<N> {
+    fn decode<D: bincode::de::Decoder<Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let mut this = Self {
+            squares: bincode::Decode::decode(decoder)?,
+            winner: bincode::Decode::decode(decoder)?,
+            cached_threats: [0; 2],
+            cached_open_lines: [0; 2],
+        };
+        this.update_threats();
+        Ok(this)
+    }
+}
+
+impl<'de, Context, const N: usize> bincode::BorrowDecode<'de, Context> for InnerBoard<N> {
+    fn borrow_decode<D: bincode::de::BorrowDecoder<'de, Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        bincode::Decode::decode(decoder)
+    }
+}
+
+// what the derives would do, without the caches
+impl<const N: usize> PartialEq for InnerBoard<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.squares == other.squares && self.winner == other.winner
+    }
+}
+
+impl<const N: usize> Eq for InnerBoard<N> {}
+
+impl<const N: usize> std::hash::Hash for InnerBoard<N> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.squares.hash(state);
+        self.winner.hash(state);
+    }
+}
+
+impl<const N: usize> PartialEq for OuterBoard<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.boards == other.boards
+            && self.overall_winner == other.overall_winner
+            && self.overall_draw == other.overall_draw
+            && self.active_square == other.active_square
+            && self.ruleset == other.ruleset
+    }
+}
+
+impl<const N: usize> Eq for OuterBoard<N> {}
+
+impl<const N: usize> std::hash::Hash for OuterBoard<N> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.boards.hash(state);
+        self.overall_winner.hash(state);
+        self.overall_draw.hash(state);
+        self.active_square.hash(state);
+        self.ruleset.hash(state);
+    }
+}
+
+impl<const N: usize> bincode::Encode for OuterBoard<N> {
+    fn encode<E: bincode::enc::Encoder>(
+        &self,
+        encoder: &mut E,
+    ) -> Result<(), bincode::error::EncodeError> {
+        self.boards.encode(encoder)?;
+        self.overall_winner.encode(encoder)?;
+        self.overall_draw.encode(encoder)?;
+        self.active_square.encode(encoder)?;
+        self.ruleset.encode(encoder)
+    }
+}
+
+impl<Context, const N: usize> bincode::Decode<Context> for OuterBoard<N> {
+    fn decode<D: bincode::de::Decoder<Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        let mut this = Self {
+            boards: bincode::Decode::decode(decoder)?,
+            overall_winner: bincode::Decode::decode(decoder)?,
+            overall_draw: bincode::Decode::decode(decoder)?,
+            active_square: bincode::Decode::decode(decoder)?,
+            ruleset: bincode::Decode::decode(decoder)?,
+            hash: 0,
+        };
+        this.hash = this.full_hash();
+        Ok(this)
+    }
+}
+
+impl<'de, Context, const N: usize> bincode::BorrowDecode<'de, Context> for OuterBoard<N> {
+    fn borrow_decode<D: bincode::de::BorrowDecoder<'de, Context = Context>>(
+        decoder: &mut D,
+    ) -> Result<Self, bincode::error::DecodeError> {
+        bincode::Decode::decode(decoder)
+    }
+}
+
+// two bits per cell, then the subboard winners, the overall result, the active square and the
+// ruleset: 162 + 18 + 3 + 4 + 1 bits, small enough to hash and compare quickly
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PackedBoard([u64; 3]);
+
+fn pack_mark(mark: Option<Mark>) -> u64 {
+    match mark {
+        None => 0,
+        Some(Mark::X) => 1,
+        Some(Mark::O) => 2,
+    }
+}
+
+fn unpack_mark(bits: u64) -> Option<Mark> {
+    match bits {
+        1 => Some(Mark::X),
+        2 => Some(Mark::O),
+        _ => None,
+    }
+}
+
+struct BitWriter {
+    words: [u64; 3],
+    position: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u64, bits: usize) {
+        for bit in 0..bits {
+            if value >> bit & 1 == 1 {
+                let position = self.position + bit;
+                self.words[position / 64] |= 1 << (position % 64);
             }
         }
+        self.position += bits;
+    }
+}
 
-        for col in 0..3 {
-            if let Some(player) = self.squares[0][col] {
-                if self.squares[1][col] == Some(player) && self.squares[2][col] == Some(player) {
-                    self.winner = Some(player);
-                    return;
-                }
+struct BitReader {
+    words: [u64; 3],
+    position: usize,
+}
+
+impl BitReader {
+    fn take(&mut self, bits: usize) -> u64 {
+        let mut value = 0;
+        for bit in 0..bits {
+            let position = self.position + bit;
+            value |= (self.words[position / 64] >> (position % 64) & 1) << bit;
+        }
+        self.position += bits;
+        value
+    }
+}
+
+impl OuterBoard {
+    // like `canonical`, but a board with the marks swapped shares it too, and by `pack`, which
+    // is quick enough for every node of a search; whether the marks were swapped comes last.
+    // It's the same position only for whoever swaps the side they're scoring for along with
+    // the marks
+    pub fn canonical_up_to_marks(&self) -> (Self, Transform, bool) {
+        [false, true]
+            .into_iter()
+            .flat_map(|swapped| {
+                let board = if swapped { self.swap_marks() } else { *self };
+                board
+                    .all_variations()
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(variation, board)| (board, Transform { variation }, swapped))
+            })
+            .min_by_key(|(board, ..)| board.pack())
+            .unwrap()
+    }
+
+    pub fn pack(&self) -> PackedBoard {
+        let mut writer = BitWriter {
+            words: [0; 3],
+            position: 0,
+        };
+        for board in self.boards.iter().flatten() {
+            for &cell in board.squares.iter().flatten() {
+                writer.push(pack_mark(cell), 2);
             }
         }
+        for board in self.boards.iter().flatten() {
+            writer.push(pack_mark(board.winner), 2);
+        }
+        writer.push(pack_mark(self.overall_winner), 2);
+        writer.push(self.overall_draw as u64, 1);
+        // 0 for free choice, otherwise 1 + the square's index
+        writer.push(
+            self.active_square
+                .map_or(0, |(r, c)| 1 + r as u64 * 3 + c as u64),
+            4,
+        );
+        writer.push(
+            match self.ruleset {
+                Ruleset::FreeChoice => 0,
+                Ruleset::WonBoardsStayOpen => 1,
+            },
+            1,
+        );
+        PackedBoard(writer.words)
+    }
 
-        if let Some(player) = self.squares[1][1] {
-            if (self.squares[0][0] == Some(player) && self.squares[2][2] == Some(player))
-                || (self.squares[0][2] == Some(player) && self.squares[2][0] == Some(player))
-            {
-                self.winner = Some(player);
-                return;
+    pub fn unpack(packed: PackedBoard) -> Self {
+        let mut reader = BitReader {
+            words: packed.0,
+            position: 0,
+        };
+        let mut this = Self::default();
+        for board in this.boards.iter_mut().flatten() {
+            for cell in board.squares.iter_mut().flatten() {
+                *cell = unpack_mark(reader.take(2));
+            }
+            board.update_threats();
+        }
+        for board in this.boards.iter_mut().flatten() {
+            board.winner = unpack_mark(reader.take(2));
+        }
+        this.overall_winner = unpack_mark(reader.take(2));
+        this.overall_draw = reader.take(1) == 1;
+        this.active_square = match reader.take(4) {
+            0 => None,
+            square => Some(((square - 1) as u8 / 3, (square - 1) as u8 % 3)),
+        };
+        this.ruleset = match reader.take(1) {
+            0 => Ruleset::FreeChoice,
+            _ => Ruleset::WonBoardsStayOpen,
+        };
+        this.hash = this.full_hash();
+        this
+    }
+
+    /// Every distinct position `n` plies into a game from the empty board, X moving first, in
+    /// the order they're first reached. With `by_symmetry` only one board of each set of
+    /// rotations and reflections is kept, the `canonical` one. Games that end early don't
+    /// carry on, so they're not included.
+    ///
+    /// The count grows ninefold or more per ply, so `n` may be at most
+    /// [`MAX_ENUMERATED_PLY`]; past that this panics.
+    ///
+    /// ```
+    /// use supertris::game::OuterBoard;
+    ///
+    /// assert_eq!(OuterBoard::positions_at_ply(2, false).count(), 720);
+    /// assert_eq!(OuterBoard::positions_at_ply(1, true).count(), 15);
+    /// ```
+    pub fn positions_at_ply(n: usize, by_symmetry: bool) -> impl Iterator<Item = Self> {
+        assert!(
+            n <= MAX_ENUMERATED_PLY,
+            "enumerating {n} plies, at most {MAX_ENUMERATED_PLY} are supported"
+        );
+        let mut layer = vec![Self::default()];
+        let mut to_move = Mark::X;
+        for _ in 0..n {
+            let mut seen = std::collections::HashSet::new();
+            layer = layer
+                .iter()
+                .filter(|board| !board.is_over())
+                .flat_map(|board| {
+                    board
+                        .possible_moves(to_move)
+                        .into_iter()
+                        .map(|r#move| board.make_move(r#move))
+                })
+                .flatten()
+                .map(|board| {
+                    if by_symmetry {
+                        board.canonical().0
+                    } else {
+                        board
+                    }
+                })
+                .filter(|board| seen.insert(board.pack()))
+                .collect();
+            to_move = !to_move;
+        }
+        layer.into_iter()
+    }
+}
+
+// random, but from a fixed seed so that hashes are the same on every run
+struct ZobristKeys {
+    // by subboard, then cell, then mark
+    cells: [[[u64; 2]; MAX_CELLS]; MAX_CELLS],
+    winners: [[u64; 2]; MAX_CELLS],
+    active_square: [u64; MAX_CELLS],
+    won_boards_stay_open: u64,
+    odd_marks: u64,
+}
+
+static ZOBRIST_KEYS: LazyLock<ZobristKeys> = LazyLock::new(|| {
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0x5afe_b0a2d);
+    ZobristKeys {
+        cells: std::array::from_fn(|_| std::array::from_fn(|_| rng.random())),
+        winners: std::array::from_fn(|_| rng.random()),
+        active_square: std::array::from_fn(|_| rng.random()),
+        won_boards_stay_open: rng.random(),
+        odd_marks: rng.random(),
+    }
+});
+
+fn square_index((r, c): (u8, u8)) -> usize {
+    r as usize * MAX_SIZE + c as usize
+}
+
+fn zobrist_cell(outer: (u8, u8), inner: (u8, u8), mark: Mark) -> u64 {
+    ZOBRIST_KEYS.cells[square_index(outer)][square_index(inner)][mark as usize]
+}
+
+fn zobrist_winner(outer: (u8, u8), winner: Option<Mark>) -> u64 {
+    winner.map_or(0, |mark| {
+        ZOBRIST_KEYS.winners[square_index(outer)][mark as usize]
+    })
+}
+
+/// The deepest ply [`OuterBoard::positions_at_ply`] enumerates: about 60 000 positions.
+pub const MAX_ENUMERATED_PLY: usize = 4;
+
+// FNV-1a: simple, and unlike std's hasher the same on every run and build
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Rows, then columns, then the two diagonals of a square grid.
+// lines one mark short of complete, with the last cell still free
+pub fn threats<T: Eq + Copy, const N: usize>(squares: [[Option<T>; N]; N], mark: T) -> usize {
+    lines(&squares)
+        .filter(|line| {
+            line.iter().filter(|&&cell| cell == Some(mark)).count() == N - 1
+                && line.iter().any(|&cell| cell.is_none())
+        })
+        .count()
+}
+
+pub fn lines<T: Copy, const N: usize>(squares: &[[T; N]; N]) -> impl Iterator<Item = [T; N]> + '_ {
+    let rows = squares.iter().copied();
+    let cols = (0..N).map(|col| std::array::from_fn(|row| squares[row][col]));
+    let diagonal = std::iter::once(std::array::from_fn(|i| squares[i][i]));
+    let anti_diagonal = std::iter::once(std::array::from_fn(|i| squares[i][N - 1 - i]));
+    rows.chain(cols).chain(diagonal).chain(anti_diagonal)
+}
+
+// one text row per cell row, with `|` and `-` between subboards and `.` for empty cells
+impl<const N: usize> std::fmt::Display for OuterBoard<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in 0..N {
+            if row > 0 {
+                writeln!(f, "{}", vec!["-".repeat(N); N].join("+"))?;
+            }
+            for inner_row in 0..N {
+                let line: Vec<String> = self.boards[row]
+                    .iter()
+                    .map(|board| {
+                        board.squares[inner_row]
+                            .iter()
+                            .map(|square| square.map_or(".".to_string(), |mark| mark.to_string()))
+                            .collect()
+                    })
+                    .collect();
+                writeln!(f, "{}", line.join("|"))?;
             }
         }
+        Ok(())
+    }
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    bincode::Encode,
+    bincode::Decode,
+    serde::Serialize,
+    serde::Deserialize,
+)]
+pub struct Move {
+    pub outer: (u8, u8),
+    pub inner: (u8, u8),
+    pub player: Mark,
+}
+
+// written as the player, the subboard and the cell, counting from 1: `X11-22`
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}{}-{}{}",
+            self.player,
+            self.outer.0 + 1,
+            self.outer.1 + 1,
+            self.inner.0 + 1,
+            self.inner.1 + 1
+        )
+    }
+}
+
+impl std::str::FromStr for Move {
+    type Err = ParseMoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let &[player, outer_row, outer_col, b'-', inner_row, inner_col] = s.as_bytes() else {
+            return Err(ParseMoveError);
+        };
+        let player = match player {
+            b'X' => Mark::X,
+            b'O' => Mark::O,
+            _ => return Err(ParseMoveError),
+        };
+        let coord = |digit: u8| match digit {
+            b'1'..=b'9' => Ok(digit - b'1'),
+            _ => Err(ParseMoveError),
+        };
+        Ok(Self {
+            outer: (coord(outer_row)?, coord(outer_col)?),
+            inner: (coord(inner_row)?, coord(inner_col)?),
+            player,
+        })
+    }
+}
+
+// everything `play` may overwrite besides the cell itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UndoInfo {
+    pub r#move: Move,
+    winner: Option<Mark>,
+    overall_winner: Option<Mark>,
+    overall_draw: bool,
+    active_square: Option<(u8, u8)>,
+}
+
+// one of the board's eight symmetries, `all_variations()[variation]`: `variation % 4` quarter
+// turns, then a reflection if it's 4 or more
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Transform {
+    pub variation: usize,
+}
+
+impl Transform {
+    pub fn inverse(self) -> Self {
+        // reflections are their own inverse
+        let variation = if self.variation >= 4 {
+            self.variation
+        } else {
+            (4 - self.variation) % 4
+        };
+        Self { variation }
+    }
+}
+
+fn rot90_coords<const N: usize>((r, c): (u8, u8)) -> (u8, u8) {
+    (c, N as u8 - 1 - r)
+}
+
+fn reflect_coords<const N: usize>((r, c): (u8, u8)) -> (u8, u8) {
+    (r, N as u8 - 1 - c)
+}
+
+impl Move {
+    // for sharing games: the subboard in capitals, then the cell, each as a column letter and
+    // a row number from the top left, so the center of the center is `B2/b2`. Unlike `Display`
+    // it leaves out the player, who follows from the order of the moves
+    pub fn to_notation(self) -> String {
+        format!(
+            "{}{}/{}{}",
+            (b'A' + self.outer.1) as char,
+            self.outer.0 + 1,
+            (b'a' + self.inner.1) as char,
+            self.inner.0 + 1
+        )
+    }
+
+    pub fn from_notation(s: &str, player: Mark) -> Result<Self, ParseNotationError> {
+        let &[outer_col, outer_row, b'/', inner_col, inner_row] = s.as_bytes() else {
+            return Err(ParseNotationError);
+        };
+        let coord = |byte: u8, first: u8| match byte.checked_sub(first) {
+            Some(coord @ 0..9) => Ok(coord),
+            _ => Err(ParseNotationError),
+        };
+        Ok(Self {
+            outer: (coord(outer_row, b'1')?, coord(outer_col, b'A')?),
+            inner: (coord(inner_row, b'1')?, coord(inner_col, b'a')?),
+            player,
+        })
+    }
+
+    // maps the move the same way `transform` maps the board
+    pub fn transformed<const N: usize>(self, transform: Transform) -> Self {
+        let mut outer = self.outer;
+        let mut inner = self.inner;
+        for _ in 0..transform.variation % 4 {
+            outer = rot90_coords::<N>(outer);
+            inner = rot90_coords::<N>(inner);
+        }
+        if transform.variation >= 4 {
+            outer = reflect_coords::<N>(outer);
+            inner = reflect_coords::<N>(inner);
+        }
+        Self {
+            outer,
+            inner,
+            ..self
+        }
+    }
+
+    pub fn untransformed<const N: usize>(self, transform: Transform) -> Self {
+        self.transformed::<N>(transform.inverse())
+    }
+}
+
+impl<const N: usize> InnerBoard<N> {
+    fn update_winner(&mut self) {
+        if self.winner.is_some() {
+            return;
+        }
+
+        self.winner = lines(&self.squares).find_map(|line| {
+            let player = line[0]?;
+            line.iter()
+                .all(|&cell| cell == Some(player))
+                .then_some(player)
+        });
     }
 
+    // whether the subboard is still being fought over; where a move can be sent is `is_open`
     pub fn can_play(&self) -> bool {
         self.winner.is_none()
             && self
@@ -101,77 +803,129 @@ impl InnerBoard {
                 .any(|row| row.iter().any(|&cell| cell.is_none()))
     }
 
-    fn possible_moves(&self) -> ArrayVec<(u8, u8), 9> {
-        let mut moves = ArrayVec::new();
-        if !self.can_play() {
-            return moves;
+    // won by `mark` already, or with a line left that has none of the other side's marks in it
+    fn winnable_by(&self, mark: Mark) -> bool {
+        match self.winner {
+            Some(winner) => winner == mark,
+            None => self.cached_open_lines[mark as usize] > 0,
         }
-        for row in 0..3 {
-            for col in 0..3 {
-                if self.squares[row][col].is_none() {
-                    moves.push((row as u8, col as u8));
-                }
-            }
+    }
+
+    // full without either side completing a line
+    pub fn is_drawn(&self) -> bool {
+        self.winner.is_none() && !self.can_play()
+    }
+
+    // whether marks can still go in, which also decides whether being sent here is free choice
+    pub fn is_open(&self, ruleset: Ruleset) -> bool {
+        match ruleset {
+            Ruleset::FreeChoice => self.can_play(),
+            Ruleset::WonBoardsStayOpen => self
+                .squares
+                .iter()
+                .any(|row| row.iter().any(|&cell| cell.is_none())),
         }
-        moves
     }
-}
 
-impl OuterBoard {
-    pub fn random(fill_percentage: f64) -> Self {
-        use rand::prelude::*;
-        let mut rng = rand::rng();
-        let mut this = OuterBoard::default();
-        this.active_square = Some((rng.random_range(0..3), rng.random_range(0..3)));
-        for row in 0..3 {
-            for col in 0..3 {
-                for inner_row in 0..3 {
-                    for inner_col in 0..3 {
-                        if rng.random_bool(fill_percentage) {
-                            this.boards[row][col].squares[inner_row][inner_col] =
-                                Some(if rng.random_bool(0.5) {
-                                    Mark::X
-                                } else {
-                                    Mark::O
-                                });
-                        }
-                    }
+    // one pass over the lines for both caches: a threat, `N - 1` marks and an empty cell, is an
+    // open line that's almost done
+    fn update_threats(&mut self) {
+        self.cached_threats = [0; 2];
+        self.cached_open_lines = [0; 2];
+        for line in lines(&self.squares) {
+            for mark in [Mark::X, Mark::O] {
+                if line.iter().all(|&cell| cell != Some(!mark)) {
+                    self.cached_open_lines[mark as usize] += 1;
+                    let marks = line.iter().filter(|&&cell| cell == Some(mark)).count();
+                    self.cached_threats[mark as usize] += (marks == N - 1) as u8;
                 }
             }
         }
+    }
+
+    pub fn threats(&self, mark: Mark) -> usize {
+        self.cached_threats[mark as usize] as usize
+    }
+
+    // the empty cells, or none at all if the subboard is closed
+    fn empty_cells(&self, ruleset: Ruleset) -> impl Iterator<Item = (u8, u8)> + '_ {
+        let open = self.is_open(ruleset);
+        (0..N)
+            .flat_map(|row| (0..N).map(move |col| (row, col)))
+            .filter(move |&(row, col)| open && self.squares[row][col].is_none())
+            .map(|(row, col)| (row as u8, col as u8))
+    }
+}
+
+impl<const N: usize> OuterBoard<N> {
+    // a position real play reaches: random legal moves from the empty board until about
+    // `fill_percentage` of the cells are filled, or the game ends first
+    pub fn random(rng: &mut impl rand::Rng, fill_percentage: f64) -> Self {
+        let plies = (fill_percentage.clamp(0.0, 1.0) * (N * N * N * N) as f64).round() as usize;
+        Self::random_playout(rng, plies).0
+    }
+
+    // the same seed and fill always give the same board, on every machine
+    pub fn random_seeded(fill_percentage: f64, seed: u64) -> Self {
+        use rand::SeedableRng;
+        Self::random(
+            &mut rand::rngs::StdRng::seed_from_u64(seed),
+            fill_percentage,
+        )
+    }
 
-        for row in 0..3 {
-            for col in 0..3 {
-                this.boards[row][col].update_winner();
+    pub fn random_playout(rng: &mut impl rand::Rng, plies: usize) -> (Self, Mark) {
+        let mut board = Self::default();
+        let mut to_move = Mark::X;
+        for _ in 0..plies {
+            if board.is_over() {
+                break;
             }
+            let moves = board.possible_moves(to_move);
+            board = board
+                .make_move(moves[rng.random_range(0..moves.len())])
+                .unwrap();
+            to_move = !to_move;
         }
-        this.update_overall_winner();
-
-        this
+        (board, to_move)
     }
 
+    // both transformations apply to the subboards and to the cells inside them alike
     fn rot90(&self) -> Self {
-        let mut new_self = Self::default();
-        new_self.active_square = self.active_square.map(|(r, c)| (c, 2 - r));
-        new_self.overall_winner = self.overall_winner;
-        for r in 0..3 {
-            for c in 0..3 {
-                let new_r = c;
-                let new_c = 2 - r;
-                new_self.boards[new_r][new_c] = self.boards[r][c];
+        let mut new_self = *self;
+        new_self.active_square = self.active_square.map(rot90_coords::<N>);
+        for r in 0..N {
+            for c in 0..N {
+                let (new_r, new_c) = rot90_coords::<N>((r as u8, c as u8));
+                let target = &mut new_self.boards[new_r as usize][new_c as usize];
+                target.winner = self.boards[r][c].winner;
+                target.cached_threats = self.boards[r][c].cached_threats;
+                target.cached_open_lines = self.boards[r][c].cached_open_lines;
+                for ir in 0..N {
+                    for ic in 0..N {
+                        let (new_ir, new_ic) = rot90_coords::<N>((ir as u8, ic as u8));
+                        target.squares[new_ir as usize][new_ic as usize] =
+                            self.boards[r][c].squares[ir][ic];
+                    }
+                }
             }
         }
+        new_self.hash = new_self.full_hash();
         new_self
     }
 
     fn reflect_vertical(&self) -> Self {
         let mut new_self = *self;
-        if let Some((r, c)) = self.active_square {
-            new_self.active_square = Some((r, 2 - c));
-        }
-        for r in 0..3 {
-            new_self.boards[r].reverse();
+        new_self.active_square = self.active_square.map(reflect_coords::<N>);
+        for row in &mut new_self.boards {
+            row.reverse();
+            for board in row {
+                for inner_row in &mut board.squares {
+                    inner_row.reverse();
+                }
+            }
         }
+        new_self.hash = new_self.full_hash();
         new_self
     }
 
@@ -188,105 +942,993 @@ impl OuterBoard {
                 variations[i + 4].write(variations[i].assume_init_read().reflect_vertical());
             }
         }
-        unsafe { transmute(variations) }
+        variations.map(|variation| unsafe { variation.assume_init() })
+    }
+
+    // the variation whose encoding sorts first, along with the transform that gives it. Every
+    // rotation and reflection of a board, active square included, has the same one
+    pub fn canonical(&self) -> (Self, Transform) {
+        let (variation, board) = self
+            .all_variations()
+            .into_iter()
+            .enumerate()
+            .min_by_key(|(_, board)| board.encode())
+            .unwrap();
+        (board, Transform { variation })
+    }
+
+    // the same position with every X an O and every O an X
+    pub fn swap_marks(&self) -> Self {
+        let mut new_self = *self;
+        for board in new_self.boards.iter_mut().flatten() {
+            for cell in board.squares.iter_mut().flatten() {
+                *cell = cell.map(|mark| !mark);
+            }
+            board.winner = board.winner.map(|mark| !mark);
+            board.cached_threats.reverse();
+            board.cached_open_lines.reverse();
+        }
+        new_self.overall_winner = self.overall_winner.map(|mark| !mark);
+        new_self.hash = new_self.full_hash();
+        new_self
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        bincode::encode_to_vec(self, bincode::config::standard())
+            .expect("encoding a board can't fail")
+    }
+
+    // over the canonical encoding, so it stays stable between runs and builds
+    pub fn canonical_hash(&self) -> u64 {
+        let (board, _) = self.canonical();
+        fnv1a(&board.encode())
     }
 
     #[must_use]
     pub fn make_move(&self, r#move: Move) -> Option<Self> {
-        if self.active_square.is_some_and(|sq| r#move.outer != sq) {
-            return None;
+        self.try_move(r#move).ok()
+    }
+
+    /// Every board of a game that starts here and goes on with `moves`, this one first, or
+    /// the first move the rules don't allow. Whose turn it is isn't checked, as `play` doesn't.
+    pub fn replay(&self, moves: &[Move]) -> Result<Vec<Self>, ReplayError> {
+        let mut boards = vec![*self];
+        for (i, &r#move) in moves.iter().enumerate() {
+            let board = boards.last().unwrap().try_move(r#move);
+            boards.push(board.map_err(|error| ReplayError {
+                ply: i + 1,
+                r#move,
+                error,
+            })?);
+        }
+        Ok(boards)
+    }
+
+    pub fn try_move(&self, r#move: Move) -> Result<Self, MoveError> {
+        let mut new_self = *self;
+        new_self.play(r#move)?;
+        Ok(new_self)
+    }
+
+    // plays in place; the returned info lets `unmake` restore the exact prior board
+    pub fn play(&mut self, r#move: Move) -> Result<UndoInfo, MoveError> {
+        let in_bounds = |(r, c): (u8, u8)| (r as usize) < N && (c as usize) < N;
+        if !in_bounds(r#move.outer) || !in_bounds(r#move.inner) {
+            return Err(MoveError::OutOfBounds);
         }
-        if self.overall_winner.is_some() {
-            return None;
+        if self.is_decided() {
+            return Err(MoveError::GameOver);
         }
-        if self.boards[r#move.outer.0 as usize][r#move.outer.1 as usize]
-            .winner
-            .is_some()
+        if let Some(expected) = self.active_square
+            && r#move.outer != expected
         {
-            return None;
+            return Err(MoveError::WrongSubboard { expected });
+        }
+        let target = &mut self.boards[r#move.outer.0 as usize][r#move.outer.1 as usize];
+        if !target.is_open(self.ruleset) {
+            return Err(MoveError::SubboardClosed);
         }
 
-        let mut new_self = *self;
-
-        let cell = &mut new_self.boards[r#move.outer.0 as usize][r#move.outer.1 as usize].squares
-            [r#move.inner.0 as usize][r#move.inner.1 as usize];
+        // before the mark goes in, or filling the last cell would look like it was full already
+        let was_closed = !target.can_play();
+        let cell = &mut target.squares[r#move.inner.0 as usize][r#move.inner.1 as usize];
         if cell.is_some() {
-            return None;
+            return Err(MoveError::CellOccupied);
         }
         *cell = Some(r#move.player);
 
-        new_self.boards[r#move.outer.0 as usize][r#move.outer.1 as usize].update_winner();
-        new_self.active_square =
-            Some(r#move.inner).filter(|&(r, c)| new_self.boards[r as usize][c as usize].can_play());
-        new_self.update_overall_winner();
+        let undo = UndoInfo {
+            r#move,
+            winner: target.winner,
+            overall_winner: self.overall_winner,
+            overall_draw: self.overall_draw,
+            active_square: self.active_square,
+        };
+
+        target.update_threats();
+        target.update_winner();
+        self.hash ^= zobrist_cell(r#move.outer, r#move.inner, r#move.player)
+            ^ ZOBRIST_KEYS.odd_marks
+            ^ zobrist_winner(r#move.outer, undo.winner)
+            ^ zobrist_winner(r#move.outer, target.winner);
+        let newly_closed = !was_closed && !target.can_play();
+        self.active_square = self.next_active_square(r#move.inner);
+        // the meta board only changes when a subboard gets won or filled
+        if newly_closed {
+            self.update_overall_winner();
+        }
 
-        Some(new_self)
+        Ok(undo)
     }
 
-    fn update_overall_winner(&mut self) {
-        if self.overall_winner.is_some() {
-            return;
+    /// Where a move into cell `inner` sends the opponent: the subboard at the same position,
+    /// unless the ruleset has it closed, in which case `None` lets them play in any open one.
+    ///
+    /// | sent to                  | `FreeChoice` | `WonBoardsStayOpen` |
+    /// |--------------------------|--------------|---------------------|
+    /// | won, with empty cells    | anywhere     | that subboard       |
+    /// | full, nobody won         | anywhere     | anywhere            |
+    /// | won and full             | anywhere     | anywhere            |
+    pub fn next_active_square(&self, inner: (u8, u8)) -> Option<(u8, u8)> {
+        Some(inner).filter(|&(r, c)| self.boards[r as usize][c as usize].is_open(self.ruleset))
+    }
+
+    pub fn unmake(&mut self, undo: UndoInfo) {
+        let UndoInfo {
+            r#move,
+            winner,
+            overall_winner,
+            overall_draw,
+            active_square,
+        } = undo;
+        let target = &mut self.boards[r#move.outer.0 as usize][r#move.outer.1 as usize];
+        target.squares[r#move.inner.0 as usize][r#move.inner.1 as usize] = None;
+        self.hash ^= zobrist_cell(r#move.outer, r#move.inner, r#move.player)
+            ^ ZOBRIST_KEYS.odd_marks
+            ^ zobrist_winner(r#move.outer, target.winner)
+            ^ zobrist_winner(r#move.outer, winner);
+        target.winner = winner;
+        target.update_threats();
+        self.overall_winner = overall_winner;
+        self.overall_draw = overall_draw;
+        self.active_square = active_square;
+    }
+
+    // for positions built by hand: derives every winner from the cells alone
+    pub fn recompute_winners(&mut self) {
+        for board in self.boards.iter_mut().flatten() {
+            board.winner = None;
+            board.update_winner();
+            board.update_threats();
         }
+        self.overall_winner = None;
+        self.overall_draw = false;
+        self.update_overall_winner();
+        self.hash = self.full_hash();
+    }
 
-        self.overall_winner = self.meta_board().winner;
+    /// A hash of the position for the transposition table, updated with every move instead
+    /// of walking the whole board: the marks, the subboard winners, the active square, the
+    /// ruleset and whether an odd number of marks has been played.
+    pub fn zobrist(&self) -> u64 {
+        let active_square = self
+            .active_square
+            .map_or(0, |square| ZOBRIST_KEYS.active_square[square_index(square)]);
+        let ruleset = match self.ruleset {
+            Ruleset::FreeChoice => 0,
+            Ruleset::WonBoardsStayOpen => ZOBRIST_KEYS.won_boards_stay_open,
+        };
+        self.hash ^ active_square ^ ruleset
     }
 
-    pub fn possible_moves(&self, player: Mark) -> ArrayVec<Move, 81> {
-        let mut moves = ArrayVec::new();
-        if let Some((outer_row, outer_col)) = self.active_square {
-            let inner_board = &self.boards[outer_row as usize][outer_col as usize];
-            for (inner_row, inner_col) in inner_board.possible_moves() {
-                moves.push(Move {
-                    outer: (outer_row, outer_col),
-                    inner: (inner_row, inner_col),
-                    player,
-                });
+    // what `hash` should be, from every cell
+    fn full_hash(&self) -> u64 {
+        let mut hash = 0;
+        let mut marks = 0;
+        for (r, row) in self.boards.iter().enumerate() {
+            for (c, board) in row.iter().enumerate() {
+                let outer = (r as u8, c as u8);
+                hash ^= zobrist_winner(outer, board.winner);
+                for (ir, cells) in board.squares.iter().enumerate() {
+                    for (ic, &cell) in cells.iter().enumerate() {
+                        if let Some(mark) = cell {
+                            hash ^= zobrist_cell(outer, (ir as u8, ic as u8), mark);
+                            marks += 1;
+                        }
+                    }
+                }
             }
+        }
+        if marks % 2 == 1 {
+            hash ^= ZOBRIST_KEYS.odd_marks;
+        }
+        hash
+    }
+
+    // how many marks are down, both sides'
+    pub fn marks(&self) -> usize {
+        self.boards
+            .iter()
+            .flatten()
+            .flat_map(|board| board.squares.iter().flatten())
+            .filter(|square| square.is_some())
+            .count()
+    }
+
+    fn count(&self, mark: Mark) -> usize {
+        self.boards
+            .iter()
+            .flatten()
+            .flat_map(|board| board.squares.iter().flatten())
+            .filter(|&&square| square == Some(mark))
+            .count()
+    }
+
+    // whoever has fewer marks is next, and X when they're even
+    pub fn side_to_move(&self) -> Mark {
+        if self.count(Mark::X) > self.count(Mark::O) {
+            Mark::O
         } else {
-            for outer_row in 0..3 {
-                for outer_col in 0..3 {
-                    let inner_board = &self.boards[outer_row][outer_col];
-                    for (inner_row, inner_col) in inner_board.possible_moves() {
-                        moves.push(Move {
+            Mark::X
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), PositionError> {
+        let x = self.count(Mark::X);
+        let o = self.count(Mark::O);
+        if x.abs_diff(o) > 1 {
+            return Err(PositionError::UnbalancedMarks { x, o });
+        }
+        for (r, row) in self.boards.iter().enumerate() {
+            for (c, board) in row.iter().enumerate() {
+                let has_line = |mark| {
+                    lines(&board.squares).any(|line| line.iter().all(|&cell| cell == Some(mark)))
+                };
+                if has_line(Mark::X) && has_line(Mark::O) {
+                    return Err(PositionError::BothSidesWon {
+                        outer: (r as u8, c as u8),
+                    });
+                }
+            }
+        }
+        if let Some((r, c)) = self.active_square
+            && !self.boards[r as usize][c as usize].is_open(self.ruleset)
+        {
+            return Err(PositionError::ActiveSubboardClosed);
+        }
+        Ok(())
+    }
+
+    pub fn any_board_open(&self) -> bool {
+        self.boards
+            .iter()
+            .flatten()
+            .any(|board| board.is_open(self.ruleset))
+    }
+
+    // a rough measure of how hard the position is to search: every legal move widens the
+    // tree, and every open threat is a line either side has to keep answering
+    pub fn complexity(&self) -> usize {
+        if self.is_over() {
+            return 0;
+        }
+        let threats: usize = self
+            .boards
+            .iter()
+            .flatten()
+            .filter(|board| board.is_open(self.ruleset))
+            .map(|board| board.threats(Mark::X) + board.threats(Mark::O))
+            .sum();
+        self.legal_cells().len() + 3 * threats
+    }
+
+    // the one move by which `other` follows from `self`, if it does
+    pub fn diff(&self, other: &Self) -> Option<Move> {
+        let mut changed = None;
+        for outer_row in 0..N {
+            for outer_col in 0..N {
+                for inner_row in 0..N {
+                    for inner_col in 0..N {
+                        let before =
+                            self.boards[outer_row][outer_col].squares[inner_row][inner_col];
+                        let after =
+                            other.boards[outer_row][outer_col].squares[inner_row][inner_col];
+                        if before == after {
+                            continue;
+                        }
+                        let (None, Some(player)) = (before, after) else {
+                            return None;
+                        };
+                        if changed.is_some() {
+                            return None;
+                        }
+                        changed = Some(Move {
                             outer: (outer_row as u8, outer_col as u8),
-                            inner: (inner_row, inner_col),
+                            inner: (inner_row as u8, inner_col as u8),
                             player,
                         });
                     }
                 }
             }
         }
-        moves
+        // the rest of the state has to follow too, not just the one cell
+        changed.filter(|&r#move| self.make_move(r#move).as_ref() == Some(other))
     }
 
-    pub fn best_move(&self, player: Mark) -> Option<(Move, i32)> {
-        searcher::Searcher::search(self, player)
+    pub fn is_decided(&self) -> bool {
+        self.overall_winner.is_some() || self.overall_draw
     }
 
-    fn meta_board(&self) -> InnerBoard {
-        let mut meta = InnerBoard::default();
-        for row in 0..3 {
-            for col in 0..3 {
-                meta.squares[row][col] = self.boards[row][col].winner;
-            }
+    pub fn is_over(&self) -> bool {
+        self.is_decided() || !self.any_board_open()
+    }
+
+    fn update_overall_winner(&mut self) {
+        if self.is_decided() {
+            return;
+        }
+
+        self.overall_winner = self.meta_board().winner;
+        self.overall_draw = self.overall_winner.is_none() && !self.any_line_achievable();
+    }
+
+    // whether some line of subboards could still all go to `mark`. Tighter than the rules'
+    // `overall_draw`: a subboard that isn't full yet is lost to a side already once every line
+    // in it holds one of the other side's marks
+    pub fn can_still_win(&self, mark: Mark) -> bool {
+        let winnable = self
+            .boards
+            .map(|row| row.map(|board| board.winnable_by(mark)));
+        lines(&winnable).any(|line| line.iter().all(|&winnable| winnable))
+    }
+
+    // a line is still achievable when every subboard on it is open or won by the same side
+    fn any_line_achievable(&self) -> bool {
+        lines(&self.meta_board_with_draws()).any(|line| {
+            [Mark::X, Mark::O].into_iter().any(|mark| {
+                line.iter()
+                    .all(|&cell| cell.is_none() || cell == Some(Ok(mark)))
+            })
+        })
+    }
+
+    /// Every cell that may be played next, as `(subboard, cell)` coordinates, whoever is to move.
+    ///
+    /// ```
+    /// use supertris::game::OuterBoard;
+    ///
+    /// let board = OuterBoard::<3>::default();
+    /// assert_eq!(board.legal_cells().len(), 81);
+    ///
+    /// let board = OuterBoard::<3> { active_square: Some((1, 1)), ..Default::default() };
+    /// assert!(board.legal_cells().iter().all(|&(outer, _)| outer == (1, 1)));
+    /// assert_eq!(board.legal_cells().len(), 9);
+    /// ```
+    pub fn legal_cells(&self) -> Vec<((u8, u8), (u8, u8))> {
+        self.possible_moves(Mark::X)
+            .iter()
+            .map(|r#move| (r#move.outer, r#move.inner))
+            .collect()
+    }
+
+    /// How many games go on from here for exactly `depth` more plies, `player` moving first
+    /// and the sides taking turns: every line of play counts once, however many lead to the
+    /// same board, and a game that ends sooner counts for nothing.
+    ///
+    /// ```
+    /// use supertris::game::{Mark, OuterBoard};
+    ///
+    /// assert_eq!(OuterBoard::<3>::default().perft(Mark::X, 2), 720);
+    /// ```
+    pub fn perft(&self, player: Mark, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
         }
+        if self.is_over() {
+            return 0;
+        }
+        self.moves(player)
+            .map(|r#move| {
+                let child = self.make_move(r#move).expect("generated moves are legal");
+                child.perft(!player, depth - 1)
+            })
+            .sum()
+    }
+
+    /// The legal cells as moves by `player`, collected on the stack; for when they're needed
+    /// more than once or in another order, otherwise [`OuterBoard::moves`] does.
+    ///
+    /// ```
+    /// use supertris::game::{Mark, OuterBoard};
+    ///
+    /// let board = OuterBoard::<3>::default();
+    /// let moves = board.possible_moves(Mark::O);
+    /// assert_eq!(moves.len(), 81);
+    /// assert!(moves.iter().all(|r#move| r#move.player == Mark::O));
+    /// ```
+    pub fn possible_moves(&self, player: Mark) -> ArrayVec<Move, MAX_MOVES> {
+        const { assert!(N <= MAX_SIZE) };
+        self.moves(player).collect()
+    }
+
+    /// The same moves as [`OuterBoard::possible_moves`] in the same order, generated one at a
+    /// time, so stopping at the first that will do skips looking at the rest.
+    pub fn moves(&self, player: Mark) -> impl Iterator<Item = Move> + '_ {
+        let (rows, cols) = match self.active_square {
+            Some((r, c)) => (r as usize..r as usize + 1, c as usize..c as usize + 1),
+            None => (0..N, 0..N),
+        };
+        rows.flat_map(move |outer_row| cols.clone().map(move |outer_col| (outer_row, outer_col)))
+            .flat_map(move |(outer_row, outer_col)| {
+                self.boards[outer_row][outer_col]
+                    .empty_cells(self.ruleset)
+                    .map(move |inner| Move {
+                        outer: (outer_row as u8, outer_col as u8),
+                        inner,
+                        player,
+                    })
+            })
+    }
+
+    fn meta_board(&self) -> InnerBoard<N> {
+        // only the winner gets read, so the caches are left empty
+        let mut meta = InnerBoard {
+            squares: self.boards.map(|row| row.map(|board| board.winner)),
+            winner: None,
+            cached_threats: [0; 2],
+            cached_open_lines: [0; 2],
+        };
         meta.update_winner();
         meta
     }
 
-    fn meta_board_with_draws(&self) -> [[Option<Result<Mark, Draw>>; 3]; 3] {
-        let mut meta: [[Option<Result<Mark, Draw>>; 3]; 3] = Default::default();
-        for row in 0..3 {
-            for col in 0..3 {
-                meta[row][col] = self.boards[row][col].winner.map(Ok).or_else(|| {
-                    if self.boards[row][col].can_play() {
+    fn meta_board_with_draws(&self) -> [[Option<Result<Mark, Draw>>; N]; N] {
+        self.boards.map(|row| {
+            row.map(|board| {
+                board.winner.map(Ok).or_else(|| {
+                    if board.can_play() {
                         None
                     } else {
                         Some(Err(Draw))
                     }
+                })
+            })
+        })
+    }
+}
+
+impl OuterBoard {
+    pub fn best_move(
+        &self,
+        player: Mark,
+        options: searcher::SearchOptions,
+    ) -> (Option<(Move, i32)>, searcher::SearchStats) {
+        searcher::Searcher::search(self, player, Default::default(), options)
+    }
+
+    // the best move and the replies the search expects after it, every one legal in turn
+    pub fn best_line(
+        &self,
+        player: Mark,
+        config: searcher::SearchConfig,
+        options: searcher::SearchOptions,
+    ) -> Vec<Move> {
+        let (best, _) = searcher::Searcher::search(self, player, config, options);
+        best.map_or_else(Vec::new, |(first, _)| {
+            searcher::Searcher::principal_variation(self, player, options, first)
+        })
+    }
+
+    pub fn analyze(
+        &self,
+        player: Mark,
+        config: searcher::SearchConfig,
+        options: searcher::SearchOptions,
+        lines: usize,
+    ) -> (Vec<(Move, i32)>, searcher::SearchStats) {
+        searcher::Searcher::search_multipv(self, player, config, options, lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    // random playouts up to 40 plies in, the same ones on every run
+    pub(in crate::game) fn positions(count: usize) -> Vec<(OuterBoard, Mark)> {
+        let mut rng = StdRng::seed_from_u64(0);
+        (0..count)
+            .map(|_| {
+                let plies = rng.random_range(0..40);
+                OuterBoard::<3>::random_playout(&mut rng, plies)
+            })
+            .collect()
+    }
+
+    // a count that only plays the cells `legal_cells` lists, through `try_move`
+    fn perft_by_cells(board: &OuterBoard, player: Mark, depth: usize) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        if board.is_over() {
+            return 0;
+        }
+        board
+            .legal_cells()
+            .into_iter()
+            .map(|(outer, inner)| {
+                let r#move = Move {
+                    outer,
+                    inner,
+                    player,
+                };
+                board
+                    .try_move(r#move)
+                    .map_or(0, |child| perft_by_cells(&child, !player, depth - 1))
+            })
+            .sum()
+    }
+
+    // any change to which moves are legal or where they send the next player shows up here
+    // first
+    #[test]
+    fn perft_from_the_empty_board() {
+        let board = OuterBoard::<3>::default();
+        let counts: Vec<_> = (0..=4).map(|depth| board.perft(Mark::X, depth)).collect();
+        assert_eq!(counts, [1, 81, 720, 6336, 55080]);
+    }
+
+    #[test]
+    fn perft_agrees_with_legal_cells() {
+        let mut rng = StdRng::seed_from_u64(55);
+        for _ in 0..20 {
+            let plies = rng.random_range(0..50);
+            let (board, to_move) = OuterBoard::<3>::random_playout(&mut rng, plies);
+            for depth in 0..=3 {
+                assert_eq!(
+                    board.perft(to_move, depth),
+                    perft_by_cells(&board, to_move, depth),
+                    "depth {depth} on\n{board}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn perft_stops_at_the_end_of_the_game() {
+        let mut board = OuterBoard::<3>::default();
+        for outer in [(0, 0), (1, 1), (2, 2)] {
+            board.boards[outer.0][outer.1].squares[0] = [Some(Mark::X); 3];
+        }
+        board.recompute_winners();
+        assert!(board.is_over());
+        assert_eq!(board.perft(Mark::O, 0), 1);
+        assert_eq!(board.perft(Mark::O, 1), 0);
+    }
+
+    #[test]
+    fn caches_dont_change_equality() {
+        use std::collections::HashSet;
+
+        let played = OuterBoard::<3>::default()
+            .make_move("X22-11".parse().unwrap())
+            .unwrap();
+        // the same cell written by hand, before anything is recomputed
+        let mut by_hand = OuterBoard::<3> {
+            active_square: Some((0, 0)),
+            ..Default::default()
+        };
+        by_hand.boards[1][1].squares[0][0] = Some(Mark::X);
+        assert_ne!(by_hand.hash, played.hash);
+        assert_eq!(by_hand, played);
+        assert_eq!(HashSet::from([by_hand, played]).len(), 1);
+    }
+
+    // every subboard given a winner gets it by its top row, the others stay empty
+    fn with_winners(winners: [[Option<Mark>; 3]; 3]) -> OuterBoard {
+        let mut board = OuterBoard::<3>::default();
+        for (row, marks) in winners.iter().enumerate() {
+            for (col, &winner) in marks.iter().enumerate() {
+                if let Some(mark) = winner {
+                    board.boards[row][col].squares[0] = [Some(mark); 3];
+                }
+            }
+        }
+        board.recompute_winners();
+        board
+    }
+
+    #[test]
+    fn every_line_blocked_for_both_is_a_draw() {
+        let (x, o) = (Some(Mark::X), Some(Mark::O));
+        let board = with_winners([[x, o, x], [x, o, o], [o, x, x]]);
+        assert_eq!(board.overall_winner, None);
+        assert!(board.overall_draw);
+        assert!(board.is_over());
+        assert!(!board.can_still_win(Mark::X));
+        assert!(!board.can_still_win(Mark::O));
+    }
+
+    #[test]
+    fn one_side_blocked_is_no_draw() {
+        let (x, o) = (Some(Mark::X), Some(Mark::O));
+        // only the bottom row is left, and it's X's
+        let board = with_winners([[x, x, o], [o, o, x], [x, None, None]]);
+        assert!(!board.overall_draw);
+        assert!(!board.is_over());
+        assert!(board.can_still_win(Mark::X));
+        assert!(!board.can_still_win(Mark::O));
+
+        // O's marks in the bottom right subboard touch every line of it, so X can't take it
+        // and with it the bottom row, though the rules still see an open line
+        let mut board = board;
+        for (row, col) in [(0, 0), (1, 1), (1, 2), (2, 1)] {
+            board.boards[2][2].squares[row][col] = Some(Mark::O);
+        }
+        board.recompute_winners();
+        assert_eq!(board.boards[2][2].winner, None);
+        assert!(!board.overall_draw);
+        assert!(!board.can_still_win(Mark::X));
+        assert!(!board.can_still_win(Mark::O));
+    }
+
+    // plays whole games in place, then unmakes them checking every intermediate board
+    #[test]
+    fn unmake_restores_every_board() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..50 {
+            let mut board = OuterBoard::<3>::default();
+            let mut to_move = Mark::X;
+            let mut history = vec![];
+            while !board.is_over() {
+                let moves = board.possible_moves(to_move);
+                let r#move = moves[rng.random_range(0..moves.len())];
+                let before = board;
+                let undo = board.play(r#move).unwrap();
+                for inner in board.boards.iter().flatten() {
+                    for mark in [Mark::X, Mark::O] {
+                        assert_eq!(
+                            inner.threats(mark),
+                            threats(inner.squares, mark),
+                            "cached threats went stale after {move}"
+                        );
+                    }
+                }
+                history.push((before, undo));
+                to_move = !to_move;
+            }
+            while let Some((before, undo)) = history.pop() {
+                board.unmake(undo);
+                assert_eq!(
+                    board, before,
+                    "unmaking {} didn't restore the board",
+                    undo.r#move
+                );
+                assert_eq!(board.hash, before.hash);
+            }
+        }
+    }
+
+    // `legal_cells` and `possible_moves` agree everywhere, and `moves` lists every cell
+    // `make_move` takes, whichever order they're generated in; it takes none once the game is
+    // decided, where the moves are still listed
+    #[test]
+    fn moves_agree_with_make_move() {
+        for (board, to_move) in positions(500) {
+            let cells: Vec<_> = board
+                .possible_moves(to_move)
+                .iter()
+                .map(|r#move| (r#move.outer, r#move.inner))
+                .collect();
+            assert_eq!(board.legal_cells(), cells, "on\n{board}");
+            if board.is_decided() {
+                continue;
+            }
+            let mut playable: Vec<_> = (0..81)
+                .map(|i: u8| Move {
+                    outer: (i / 27, i / 3 % 3),
+                    inner: (i / 9 % 3, i % 3),
+                    player: to_move,
+                })
+                .filter(|&r#move| board.make_move(r#move).is_some())
+                .collect();
+            let mut generated: Vec<_> = board.moves(to_move).collect();
+            generated.sort_by_key(|r#move| (r#move.outer, r#move.inner));
+            playable.sort_by_key(|r#move| (r#move.outer, r#move.inner));
+            assert_eq!(generated, playable, "on\n{board}");
+        }
+    }
+
+    // the hash kept up move by move, and taken back by `unmake`, matches the one worked out
+    // from the whole board
+    #[test]
+    fn zobrist_matches_a_fresh_hash() {
+        let fresh = |board: &OuterBoard| {
+            let mut fresh = *board;
+            fresh.recompute_winners();
+            fresh.zobrist()
+        };
+        let mut rng = StdRng::seed_from_u64(13);
+        for _ in 0..50 {
+            let mut board = OuterBoard::<3>::default();
+            let mut to_move = Mark::X;
+            while !board.is_over() {
+                let legal = board.possible_moves(to_move);
+                let before = board.zobrist();
+                let undo = board.play(legal[rng.random_range(0..legal.len())]).unwrap();
+                assert_eq!(board.zobrist(), fresh(&board), "after {undo:?}\n{board}");
+                let mut undone = board;
+                undone.unmake(undo);
+                assert_eq!(undone.zobrist(), before, "unmake didn't restore the hash");
+                to_move = !to_move;
+            }
+        }
+        for (board, _) in positions(500) {
+            assert_eq!(board.zobrist(), fresh(&board), "on\n{board}");
+        }
+    }
+
+    #[test]
+    fn zobrist_tells_apart_the_positions_three_plies_in() {
+        let positions: Vec<_> = OuterBoard::<3>::positions_at_ply(3, false).collect();
+        let hashes: std::collections::HashSet<u64> =
+            positions.iter().map(|board| board.zobrist()).collect();
+        assert_eq!(hashes.len(), positions.len());
+    }
+
+    // packing has to lose nothing, threat caches included, on played and random boards alike
+    #[test]
+    fn packing_round_trips() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let random = (0..500).map(|_| {
+            let fill = rng.random_range(0.0..1.0);
+            let mut board = OuterBoard::<3>::random(&mut rng, fill);
+            board.ruleset = Ruleset::WonBoardsStayOpen;
+            board
+        });
+        let played = positions(500).into_iter().map(|(board, _)| board);
+        for board in played.chain(random) {
+            let unpacked = OuterBoard::unpack(board.pack());
+            assert_eq!(unpacked, board);
+            for (a, b) in unpacked
+                .boards
+                .iter()
+                .flatten()
+                .zip(board.boards.iter().flatten())
+            {
+                assert_eq!(a.threats(Mark::X), b.threats(Mark::X), "on\n{board}");
+                assert_eq!(a.threats(Mark::O), b.threats(Mark::O), "on\n{board}");
+            }
+        }
+    }
+
+    // `diff` has to recover every move of a playout, and nothing from boards two moves apart
+    #[test]
+    fn diff_finds_every_move() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let mut boards = vec![OuterBoard::<3>::default()];
+            let mut to_move = Mark::X;
+            while !boards.last().unwrap().is_over() {
+                let board = *boards.last().unwrap();
+                let legal = board.possible_moves(to_move);
+                let r#move = legal[rng.random_range(0..legal.len())];
+                let next = board.try_move(r#move).unwrap();
+                assert_eq!(board.diff(&next), Some(r#move), "on\n{board}");
+                assert_eq!(board.diff(&board), None);
+                assert_eq!(next.diff(&board), None);
+                if let Some(before) = boards.iter().rev().nth(1) {
+                    assert_eq!(before.diff(&next), None, "two moves apart");
+                }
+                boards.push(next);
+                to_move = !to_move;
+            }
+        }
+    }
+
+    // a seed and a fill always give the same board
+    #[test]
+    fn random_seeded_is_reproducible() {
+        let mut distinct = std::collections::HashSet::new();
+        for seed in 0..50 {
+            let fill = seed as f64 / 50.0;
+            let board = OuterBoard::<3>::random_seeded(fill, seed);
+            assert_eq!(board, OuterBoard::<3>::random_seeded(fill, seed));
+            distinct.insert(board);
+        }
+        assert!(
+            distinct.len() >= 45,
+            "only {} different boards",
+            distinct.len()
+        );
+    }
+
+    // whatever the fill, a random board is one real play could reach: the marks alternate, no
+    // subboard or meta board has a line for both sides, and nobody moved after a meta line
+    #[test]
+    fn random_boards_are_reachable() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for i in 0..400 {
+            let fill = (i % 20) as f64 / 19.0;
+            let board = OuterBoard::<3>::random(&mut rng, fill);
+            let (x, o) = (board.count(Mark::X), board.count(Mark::O));
+            assert!(x == o || x == o + 1, "{x} X and {o} O at {fill}\n{board}");
+            let has_line = |squares: &[[Option<Mark>; 3]; 3], mark| {
+                lines(squares).any(|line| line.iter().all(|&cell| cell == Some(mark)))
+            };
+            for inner in board.boards.iter().flatten() {
+                let both = has_line(&inner.squares, Mark::X) && has_line(&inner.squares, Mark::O);
+                assert!(!both, "a subboard won by both at {fill}\n{board}");
+            }
+            let meta = board.boards.map(|row| row.map(|inner| inner.winner));
+            let (meta_x, meta_o) = (has_line(&meta, Mark::X), has_line(&meta, Mark::O));
+            assert!(
+                !(meta_x && meta_o),
+                "a meta line for both at {fill}\n{board}"
+            );
+            let last_mover = if x > o { Mark::X } else { Mark::O };
+            if meta_x || meta_o {
+                assert_eq!(board.overall_winner, Some(last_mover), "at {fill}\n{board}");
+            }
+            let plies = (fill * 81.0).round() as usize;
+            assert!(
+                board.is_over() || x + o == plies,
+                "{} marks at {fill}",
+                x + o
+            );
+            if let Some((r, c)) = board.active_square {
+                assert!(board.boards[r as usize][c as usize].is_open(board.ruleset));
+            }
+        }
+    }
+
+    // being sent to a closed subboard means free choice, and which subboards are closed is the
+    // one thing the rulesets disagree on
+    #[test]
+    fn closed_targets_depend_on_the_ruleset() {
+        use Mark::{O, X};
+        let cases = [
+            (
+                "won with empty cells",
+                [
+                    [Some(X), Some(X), Some(X)],
+                    [Some(O), Some(O), None],
+                    [None; 3],
+                ],
+                None,
+                Some((1, 1)),
+            ),
+            (
+                "full and drawn",
+                [[X, O, X], [X, O, O], [O, X, X]].map(|row| row.map(Some)),
+                None,
+                None,
+            ),
+            (
+                "won and full",
+                [[X, X, X], [O, O, X], [X, O, O]].map(|row| row.map(Some)),
+                None,
+                None,
+            ),
+        ];
+        for (name, squares, free_choice, stay_open) in cases {
+            for (ruleset, expected) in [
+                (Ruleset::FreeChoice, free_choice),
+                (Ruleset::WonBoardsStayOpen, stay_open),
+            ] {
+                let mut board = OuterBoard {
+                    ruleset,
+                    ..Default::default()
+                };
+                board.boards[1][1].squares = squares;
+                board.recompute_winners();
+                board.active_square = Some((0, 0));
+                assert_eq!(
+                    board.next_active_square((1, 1)),
+                    expected,
+                    "{name}, {ruleset:?}"
+                );
+                let played = board.make_move(Move {
+                    outer: (0, 0),
+                    inner: (1, 1),
+                    player: X,
                 });
+                assert_eq!(
+                    played.expect("the move there is legal").active_square,
+                    expected,
+                    "{name}, {ruleset:?}"
+                );
             }
         }
-        meta
+    }
+
+    // every cell survives the shared notation, the documented one included
+    #[test]
+    fn notation_round_trips() {
+        for i in 0..81u8 {
+            let r#move = Move {
+                outer: (i / 27, i / 3 % 3),
+                inner: (i / 9 % 3, i % 3),
+                player: Mark::O,
+            };
+            assert_eq!(
+                Move::from_notation(&r#move.to_notation(), Mark::O),
+                Ok(r#move)
+            );
+        }
+        let center = Move {
+            outer: (1, 1),
+            inner: (1, 1),
+            player: Mark::X,
+        };
+        assert_eq!(center.to_notation(), "B2/b2");
+        for bad in ["", "B2b2", "b2/B2", "B0/b1", "B2/b2 "] {
+            assert!(
+                Move::from_notation(bad, Mark::X).is_err(),
+                "\"{bad}\" parsed"
+            );
+        }
+    }
+
+    // a game replays from its moves alone, up to the first move the rules turn down
+    #[test]
+    fn replay_stops_at_the_first_illegal_move() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut board = OuterBoard::<3>::default();
+        let mut moves = vec![];
+        let mut to_move = Mark::X;
+        while moves.len() < 40 && !board.is_over() {
+            let legal = board.possible_moves(to_move);
+            let r#move = legal[rng.random_range(0..legal.len())];
+            board = board.try_move(r#move).unwrap();
+            moves.push(r#move);
+            to_move = !to_move;
+        }
+        let replayed = OuterBoard::<3>::default().replay(&moves).unwrap();
+        assert_eq!(replayed.len(), moves.len() + 1);
+        assert_eq!(replayed.last(), Some(&board));
+        // the same move twice is turned down the second time, and that's the ply reported
+        moves[20] = moves[10];
+        match OuterBoard::<3>::default().replay(&moves) {
+            Err(ReplayError { ply: 21, .. }) => {}
+            result => panic!("a repeated move replayed as {result:?}"),
+        }
+    }
+
+    // every rotation and reflection of a position has the same canonical board, and the
+    // transform maps the position and its moves onto it
+    #[test]
+    fn symmetric_positions_share_a_canonical_board() {
+        let mut rng = StdRng::seed_from_u64(21);
+        for _ in 0..100 {
+            let plies = rng.random_range(0..40);
+            let (board, to_move) = OuterBoard::<3>::random_playout(&mut rng, plies);
+            let (canonical, transform) = board.canonical();
+            assert_eq!(board.all_variations()[transform.variation], canonical);
+            for (variation, symmetric) in board.all_variations().into_iter().enumerate() {
+                assert_eq!(symmetric.canonical().0, canonical, "variation {variation}");
+            }
+            for r#move in board.possible_moves(to_move) {
+                let played = board.make_move(r#move).unwrap();
+                let mapped = canonical.make_move(r#move.transformed::<3>(transform));
+                assert_eq!(mapped, Some(played.all_variations()[transform.variation]));
+            }
+        }
+    }
+
+    // the first plies can be counted by hand: X anywhere, then O in any free cell of the
+    // subboard it points to, one fewer for the 9 moves that point back into their own subboard
+    #[test]
+    fn positions_at_ply_count_by_hand() {
+        let counts: Vec<_> = (0..3)
+            .map(|n| OuterBoard::<3>::positions_at_ply(n, false).count())
+            .collect();
+        assert_eq!(counts, [1, 81, 720]);
+        assert_eq!(OuterBoard::<3>::positions_at_ply(1, true).count(), 15);
+        for board in OuterBoard::<3>::positions_at_ply(3, true) {
+            assert_eq!(board.marks(), 3, "on\n{board}");
+        }
     }
 }
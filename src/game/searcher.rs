@@ -1,57 +1,870 @@
 use std::sync::{
-    OnceLock,
-    atomic::{AtomicUsize, Ordering},
+    Arc, LazyLock, Mutex, OnceLock,
+    atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
+use arrayvec::ArrayVec;
 use dashmap::DashMap;
 use rayon::prelude::*;
 use tracing::debug;
 
-use super::{InnerBoard, Mark, Move, OuterBoard};
+use super::{MAX_MOVES, Mark, Move, OuterBoard, Variant, lines, threats};
+
+// far above any score a real position gets, far below where sums of them could overflow
+pub const DEFAULT_EVAL_CLAMP: i32 = 100_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct HeuristicWeights {
+    // multiplies every term computed on the meta board, trading local tactics for the
+    // overall game
+    pub meta_multiplier: i32,
+    pub threat: i32,
+    pub subboard_win: i32,
+    pub center: i32,
+    pub edge: i32,
+    pub corner: i32,
+    pub free_choice: i32,
+    // percentages scaling everything a subboard scores on its own by where it sits on the meta
+    // board: the center subboard has four winning lines through it, corners three, edges two
+    pub center_subboard: i32,
+    pub corner_subboard: i32,
+    pub edge_subboard: i32,
+    // for the side that still has a line of subboards it could win when the other has none,
+    // so at best the other can hold a draw
+    pub meta_unwinnable: i32,
+    // positions that aren't over never score beyond this either way, so `i32::MAX` and
+    // `i32::MIN` stay for won and lost games
+    pub eval_clamp: i32,
+}
+
+impl Default for HeuristicWeights {
+    fn default() -> Self {
+        Self {
+            meta_multiplier: 5,
+            threat: 100,
+            subboard_win: 1000,
+            center: 10,
+            edge: 5,
+            corner: 2,
+            free_choice: 200,
+            center_subboard: 120,
+            corner_subboard: 110,
+            edge_subboard: 100,
+            meta_unwinnable: 20_000,
+            eval_clamp: DEFAULT_EVAL_CLAMP,
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum Personality {
+    #[default]
+    Balanced,
+    Aggressive,
+    Positional,
+    Solid,
+}
+
+impl Personality {
+    pub const ALL: [Self; 4] = [
+        Self::Balanced,
+        Self::Aggressive,
+        Self::Positional,
+        Self::Solid,
+    ];
+
+    pub fn weights(self) -> HeuristicWeights {
+        let balanced = HeuristicWeights::default();
+        match self {
+            Self::Balanced => balanced,
+            Self::Aggressive => HeuristicWeights {
+                threat: 250,
+                free_choice: 100,
+                ..balanced
+            },
+            Self::Positional => HeuristicWeights {
+                center: 25,
+                edge: 12,
+                corner: 5,
+                threat: 60,
+                ..balanced
+            },
+            Self::Solid => HeuristicWeights {
+                meta_multiplier: 10,
+                subboard_win: 1500,
+                ..balanced
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Personality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Balanced => "Equilibrato",
+            Self::Aggressive => "Aggressivo",
+            Self::Positional => "Posizionale",
+            Self::Solid => "Solido",
+        })
+    }
+}
+
+// how long and how deep the computer gets to think about its own moves
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Self; 3] = [Self::Easy, Self::Medium, Self::Hard];
+
+    pub fn config(self) -> SearchConfig {
+        match self {
+            Self::Easy => SearchConfig {
+                max_depth: 4,
+                max_time: std::time::Duration::from_millis(50),
+                cancel: None,
+            },
+            Self::Medium => SearchConfig::default(),
+            Self::Hard => SearchConfig {
+                max_depth: MAX_DEPTH,
+                max_time: std::time::Duration::from_millis(500),
+                cancel: None,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Easy => "Facile",
+            Self::Medium => "Medio",
+            Self::Hard => "Difficile",
+        })
+    }
+}
+
+// everything that changes what a search returns, besides the board and the side to move
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SearchOptions {
+    pub variant: Variant,
+    // only this many moves per node, the statically most promising ones, get searched
+    pub max_moves_considered: Option<usize>,
+    pub weights: HeuristicWeights,
+    // how much worse than even a draw is for the side searching; negative seeks them out
+    pub contempt: i32,
+    // moves get searched as `possible_moves` lists them, instead of likeliest first; only
+    // there to measure the move ordering against
+    pub generation_order: bool,
+}
+
+impl SearchOptions {
+    // what a drawn game is worth to the side searching
+    pub fn draw_score(&self) -> i32 {
+        self.contempt.saturating_neg()
+    }
+
+    // stands in for the options in every table key, so they're hashed once per search
+    pub fn fingerprint(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::hash::DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+// how a finished game went for the side searching; with no legal moves left for anyone the
+// game is a draw, never a loss for whoever was stuck
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Terminal {
+    Win,
+    Loss,
+    Draw,
+}
+
+impl Terminal {
+    pub fn of(board: &OuterBoard, player: Mark, variant: Variant) -> Option<Self> {
+        // the winning move may close the last open subboard, so the winner goes first
+        match board.overall_winner {
+            Some(line_owner) if variant.winner(line_owner) == player => Some(Self::Win),
+            Some(_) => Some(Self::Loss),
+            None if board.is_over() => Some(Self::Draw),
+            None => None,
+        }
+    }
+}
 
 pub struct Searcher {
     pub start_time: std::time::Instant,
     pub player: Mark,
+    pub options: SearchOptions,
+    // `options.fingerprint()`
+    pub options_fingerprint: u64,
+    pub max_depth: usize,
+    // single-threaded, in move generation order and without the time limit
+    pub deterministic: bool,
+    // after which every branch stops at its heuristic
+    pub max_time: std::time::Duration,
+    pub cancel: Option<Arc<AtomicBool>>,
+    pub nodes: AtomicUsize,
+    pub deepest: AtomicUsize,
+    pub timed_out: AtomicBool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchStats {
+    pub nodes: usize,
+    // the deepest ply any branch got to before hitting a leaf or the time limit
+    pub depth: usize,
+    pub elapsed: std::time::Duration,
+    // set when the move was picked before searching anything
+    pub shortcut: Option<Shortcut>,
+    // some branch was cut short by the time limit, so not all of it is `depth` deep
+    pub timed_out: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shortcut {
+    // the move ends the game in the mover's favour
+    Win,
+    // every other move lets the opponent win on their next one
+    OnlyDefense,
+    // there's no other legal move
+    OnlyMove,
+}
+
+// something the side to move can grab with a single move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trap {
+    // the move wins a subboard
+    Subboard(Move),
+    // the move leaves two lines in its subboard one mark from a win
+    DoubleThreat(Move),
+}
+
+impl Trap {
+    pub fn r#move(self) -> Move {
+        match self {
+            Self::Subboard(r#move) | Self::DoubleThreat(r#move) => r#move,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct TTableKey {
-    pub board: OuterBoard,
+    // `OuterBoard::zobrist`, of `OuterBoard::canonical_up_to_marks` early in the game
+    pub board: u64,
     pub maximizing: bool,
     pub player: Mark,
+    // `SearchOptions::fingerprint`
+    pub options: u64,
+}
+
+// symmetric positions mostly meet in the opening; past this many marks the 16 boards
+// `canonical_up_to_marks` builds cost more per node than the entries they share save
+pub const CANONICAL_KEY_MARKS: usize = 8;
+
+impl TTableKey {
+    // rotations, reflections and swapping the marks don't change what a position is worth, so
+    // in the opening all of them share one entry. With the marks swapped, `player` is too: the
+    // eval is the other side's on the other side's board
+    pub fn new(node: &OuterBoard, maximizing: bool, player: Mark, options: u64) -> Self {
+        if node.marks() > CANONICAL_KEY_MARKS {
+            return Self {
+                board: node.zobrist(),
+                maximizing,
+                player,
+                options,
+            };
+        }
+        let (canonical, _, swapped) = node.canonical_up_to_marks();
+        Self {
+            board: canonical.zobrist(),
+            maximizing,
+            player: if swapped { !player } else { player },
+            options,
+        }
+    }
+}
+
+// alpha-beta only proves the exact value inside the window; outside it, one side of it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bound {
+    Exact,
+    // the value is at least `eval`: it failed high
+    Lower,
+    // the value is at most `eval`: it failed low
+    Upper,
 }
 
 pub struct TTableValue {
     pub eval: i32,
     pub depth: usize,
+    pub bound: Bound,
     pub hits: AtomicUsize,
 }
 
+impl TTableValue {
+    // whether a search to `depth` with this window would come back with `eval` too
+    fn answers(&self, depth: usize, alpha: i32, beta: i32) -> bool {
+        self.depth >= depth
+            && match self.bound {
+                Bound::Exact => true,
+                Bound::Lower => self.eval >= beta,
+                Bound::Upper => self.eval <= alpha,
+            }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HeuristicBreakdown {
+    pub terminal: Option<i32>,
+    pub meta_control: i32,
+    pub meta_threats: i32,
+    pub subboard_wins: i32,
+    pub threats: i32,
+    pub center: i32,
+    pub edges: i32,
+    pub corners: i32,
+    pub free_choice: i32,
+    pub unwinnable: i32,
+    // from `HeuristicWeights::eval_clamp`
+    pub clamp: i32,
+}
+
+impl HeuristicBreakdown {
+    // everything but the free choice bonus flips sign when lines are bad
+    fn inverted(self) -> Self {
+        Self {
+            terminal: self.terminal,
+            meta_control: self.meta_control.saturating_neg(),
+            meta_threats: self.meta_threats.saturating_neg(),
+            subboard_wins: self.subboard_wins.saturating_neg(),
+            threats: self.threats.saturating_neg(),
+            center: self.center.saturating_neg(),
+            edges: self.edges.saturating_neg(),
+            corners: self.corners.saturating_neg(),
+            free_choice: self.free_choice,
+            unwinnable: self.unwinnable.saturating_neg(),
+            clamp: self.clamp,
+        }
+    }
+
+    pub fn total(&self) -> i32 {
+        self.terminal.unwrap_or_else(|| {
+            [
+                self.meta_threats,
+                self.subboard_wins,
+                self.threats,
+                self.center,
+                self.edges,
+                self.corners,
+                self.free_choice,
+                self.unwinnable,
+            ]
+            .into_iter()
+            .fold(self.meta_control, i32::saturating_add)
+            .clamp(-self.clamp, self.clamp)
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CellKind {
+    Center,
+    Edge,
+    Corner,
+}
+
+impl CellKind {
+    fn of<const N: usize>(r: usize, c: usize) -> Self {
+        let r_border = r == 0 || r == N - 1;
+        let c_border = c == 0 || c == N - 1;
+        match (r_border, c_border) {
+            (true, true) => Self::Corner,
+            (false, false) => Self::Center,
+            _ => Self::Edge,
+        }
+    }
+}
+
+enum Immediate {
+    Move(Move, Shortcut),
+    Safe(Vec<Move>),
+    Nothing,
+}
+
 pub static TRANSPOSITION_TABLE: OnceLock<DashMap<TTableKey, TTableValue>> = OnceLock::new();
 
-const MAX_DEPTH: usize = 16;
-const MAX_SEARCH_TIME: f64 = 0.25; // seconds
+pub const MAX_DEPTH: usize = 16;
+pub const MAX_SEARCH_TIME: std::time::Duration = std::time::Duration::from_millis(250);
+
+// how deep and for how long one search may look, `MAX_DEPTH` and `MAX_SEARCH_TIME` unless
+// the caller says otherwise
+#[derive(Clone, Debug)]
+pub struct SearchConfig {
+    pub max_depth: usize,
+    pub max_time: std::time::Duration,
+    // once set, every branch stops at its heuristic as if the time had run out
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_DEPTH,
+            max_time: MAX_SEARCH_TIME,
+            cancel: None,
+        }
+    }
+}
+
+// caps for small machines and CI. Both start out from the environment:
+//   SUPERTRIS_THREADS     threads the root moves get spread over, instead of one per core
+//   SUPERTRIS_TT_ENTRIES  how big the transposition table may get; it's checked before every
+//                         search and cleared once past it, so one search can still go over
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceLimits {
+    pub threads: Option<usize>,
+    pub max_table_entries: Option<usize>,
+}
+
+impl ResourceLimits {
+    pub fn from_env() -> Self {
+        let var = |name| {
+            let value = std::env::var(name).ok()?;
+            match value.parse() {
+                Ok(0) | Err(_) => {
+                    tracing::warn!(name, value, "resource_limit_ignored");
+                    None
+                }
+                Ok(n) => Some(n),
+            }
+        };
+        Self {
+            threads: var("SUPERTRIS_THREADS"),
+            max_table_entries: var("SUPERTRIS_TT_ENTRIES"),
+        }
+    }
+}
+
+static RESOURCE_LIMITS: LazyLock<Mutex<ResourceLimits>> =
+    LazyLock::new(|| Mutex::new(ResourceLimits::from_env()));
+// built for the last thread cap asked for, so searches don't each spawn their own threads
+static THREAD_POOL: Mutex<Option<(usize, Arc<rayon::ThreadPool>)>> = Mutex::new(None);
+
+pub fn resource_limits() -> ResourceLimits {
+    *RESOURCE_LIMITS.lock().unwrap()
+}
+
+// takes effect from the next search
+pub fn set_resource_limits(limits: ResourceLimits) {
+    *RESOURCE_LIMITS.lock().unwrap() = limits;
+}
+
+fn thread_pool(threads: usize) -> Arc<rayon::ThreadPool> {
+    let mut pool = THREAD_POOL.lock().unwrap();
+    if let Some((built_for, pool)) = &*pool
+        && *built_for == threads
+    {
+        return Arc::clone(pool);
+    }
+    let built = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("search-{i}"))
+            .build()
+            .expect("failed to build the search thread pool"),
+    );
+    *pool = Some((threads, Arc::clone(&built)));
+    built
+}
+
+pub fn clear_transposition_table() {
+    if let Some(table) = TRANSPOSITION_TABLE.get() {
+        table.clear();
+    }
+}
 
 impl Searcher {
-    pub fn search(board: &OuterBoard, player: Mark) -> Option<(Move, i32)> {
-        TRANSPOSITION_TABLE.get_or_init(|| DashMap::new());
+    pub fn search(
+        board: &OuterBoard,
+        player: Mark,
+        config: SearchConfig,
+        options: SearchOptions,
+    ) -> (Option<(Move, i32)>, SearchStats) {
+        let (mut moves, stats) = Self::search_multipv(board, player, config, options, 1);
+        (moves.pop(), stats)
+    }
+
+    // the same answer on every run, as long as the transposition table is cleared first
+    pub fn search_deterministic(
+        board: &OuterBoard,
+        player: Mark,
+        max_depth: usize,
+        options: SearchOptions,
+    ) -> (Option<(Move, i32)>, SearchStats) {
+        let config = SearchConfig {
+            max_depth,
+            max_time: std::time::Duration::MAX,
+            cancel: None,
+        };
+        let (mut moves, stats) = Self::root(board, player, config, options, 1, true);
+        (moves.pop(), stats)
+    }
+
+    // every root move gets a full window, so the values of the runners-up are exact too.
+    // One depth after the other, each with the time the earlier ones left, answering with the
+    // deepest that finished; depth 1 always does, since it's the heuristic of every move, so
+    // neither the clock nor a cancel stops it
+    pub fn search_multipv(
+        board: &OuterBoard,
+        player: Mark,
+        config: SearchConfig,
+        options: SearchOptions,
+        count: usize,
+    ) -> (Vec<(Move, i32)>, SearchStats) {
+        let start = std::time::Instant::now();
+        let mut best = vec![];
+        let mut total = SearchStats {
+            nodes: 0,
+            depth: 0,
+            elapsed: std::time::Duration::ZERO,
+            shortcut: None,
+            timed_out: false,
+        };
+        for depth in 1..=config.max_depth.max(1) {
+            let left = match depth {
+                1 => std::time::Duration::MAX,
+                _ => config.max_time.saturating_sub(start.elapsed()),
+            };
+            if left.is_zero() {
+                total.timed_out = true;
+                break;
+            }
+            let iteration = SearchConfig {
+                max_depth: depth,
+                max_time: left,
+                cancel: (depth > 1).then(|| config.cancel.clone()).flatten(),
+            };
+            let (moves, stats) = Self::root(board, player, iteration, options, count, false);
+            total.nodes += stats.nodes;
+            if stats.timed_out {
+                debug!(depth, "iteration_timed_out");
+                total.timed_out = true;
+                break;
+            }
+            best = moves;
+            total.depth = stats.depth;
+            total.shortcut = stats.shortcut;
+            // nothing deeper changes a shortcut or a proven result, though the runners-up of a
+            // multi-line search still have their own values to settle
+            let proven = count == 1
+                && best
+                    .first()
+                    .is_some_and(|&(_, value)| value == i32::MAX || value == i32::MIN);
+            // and with the game over sooner everywhere, there's nothing deeper to see
+            if stats.shortcut.is_some() || proven || stats.depth < depth {
+                break;
+            }
+        }
+        total.elapsed = start.elapsed();
+        (best, total)
+    }
+
+    // the line the last search expects after `first`, read back out of the transposition
+    // table: at every ply the child it scored best for whoever moves there. It ends at a
+    // terminal node or as soon as no child was searched
+    pub fn principal_variation(
+        board: &OuterBoard,
+        player: Mark,
+        options: SearchOptions,
+        first: Move,
+    ) -> Vec<Move> {
+        let Some(table) = TRANSPOSITION_TABLE.get() else {
+            return vec![];
+        };
+        let Some(mut node) = board.make_move(first) else {
+            return vec![];
+        };
+        let mut line = vec![first];
+        let mut maximizing = false;
+        while line.len() < MAX_DEPTH && !node.is_over() {
+            let mover = if maximizing { player } else { !player };
+            let scored = node.moves(mover).filter_map(|r#move| {
+                let child = node.make_move(r#move)?;
+                let key = TTableKey::new(&child, !maximizing, player, options.fingerprint());
+                let eval = table.get(&key)?.eval;
+                Some((
+                    r#move,
+                    child,
+                    if maximizing {
+                        eval
+                    } else {
+                        eval.saturating_neg()
+                    },
+                ))
+            });
+            let Some((r#move, child, _)) = scored.max_by_key(|&(_, _, eval)| eval) else {
+                break;
+            };
+            line.push(r#move);
+            node = child;
+            maximizing = !maximizing;
+        }
+        line
+    }
+
+    fn root(
+        board: &OuterBoard,
+        player: Mark,
+        config: SearchConfig,
+        options: SearchOptions,
+        count: usize,
+        deterministic: bool,
+    ) -> (Vec<(Move, i32)>, SearchStats) {
+        let limits = resource_limits();
+        let table = TRANSPOSITION_TABLE.get_or_init(DashMap::new);
+        if let Some(max) = limits.max_table_entries
+            && table.len() > max
+        {
+            debug!(entries = table.len(), max, "transposition_table_over_limit");
+            table.clear();
+        }
 
         let searcher = Self {
             start_time: std::time::Instant::now(),
             player,
+            options,
+            options_fingerprint: options.fingerprint(),
+            max_depth: config.max_depth,
+            deterministic,
+            max_time: config.max_time,
+            cancel: config.cancel,
+            nodes: AtomicUsize::new(0),
+            deepest: AtomicUsize::new(0),
+            timed_out: AtomicBool::new(false),
         };
 
-        board
-            .possible_moves(player)
-            .into_par_iter()
+        // a single best move is all that's asked for, so what the board says outright goes first
+        let mut safe = None;
+        if count == 1 {
+            match Self::immediate(board, player, options.variant) {
+                Immediate::Move(r#move, shortcut) => {
+                    let value = match shortcut {
+                        Shortcut::Win => i32::MAX,
+                        Shortcut::OnlyDefense | Shortcut::OnlyMove => Self::heuristic(
+                            &board.make_move(r#move).unwrap(),
+                            player,
+                            !player,
+                            options.variant,
+                            &options.weights,
+                            options.draw_score(),
+                        ),
+                    };
+                    debug!("move" = ?r#move, ?shortcut, "root_shortcut");
+                    let stats = SearchStats {
+                        nodes: 0,
+                        depth: 1,
+                        elapsed: searcher.start_time.elapsed(),
+                        shortcut: Some(shortcut),
+                        timed_out: false,
+                    };
+                    return (vec![(r#move, value)], stats);
+                }
+                Immediate::Safe(moves) => safe = Some(moves),
+                Immediate::Nothing => {}
+            }
+        }
+
+        let evaluate = |&r#move: &Move| {
+            let value = board.make_move(r#move).map_or(i32::MIN, |child| {
+                searcher.branch(&child, searcher.max_depth - 1, false, i32::MIN, i32::MAX)
+            });
+            debug!("move" = ?r#move, "value" = value, "computer_move_opportunity");
+            (r#move, value)
+        };
+        let mut moves = searcher.candidate_moves(board, player);
+        if let Some(safe) = safe {
+            // the move limit may have pruned every safe move, and those are the ones that matter
+            moves.retain(|r#move| safe.contains(r#move));
+            if moves.is_empty() {
+                moves = safe.into_iter().collect();
+            }
+        }
+        let mut results: Vec<(Move, i32)> = if deterministic {
+            moves.iter().map(evaluate).collect()
+        } else if let Some(threads) = limits.threads {
+            thread_pool(threads).install(|| moves.par_iter().map(evaluate).collect())
+        } else {
+            moves.par_iter().map(evaluate).collect()
+        };
+        // ties go to the later move, the same one `max_by_key` would pick
+        results.reverse();
+        results.sort_by_key(|&(_, value)| std::cmp::Reverse(value));
+        results.truncate(count);
+
+        let stats = SearchStats {
+            nodes: searcher.nodes.load(Ordering::Relaxed),
+            depth: searcher.deepest.load(Ordering::Relaxed),
+            elapsed: searcher.start_time.elapsed(),
+            shortcut: None,
+            timed_out: searcher.timed_out.load(Ordering::Relaxed),
+        };
+        (results, stats)
+    }
+
+    // one ply and the opponent's reply, without the heuristic: a winning move, else the moves
+    // that don't lose straight away if that's only some of them
+    fn immediate(board: &OuterBoard, player: Mark, variant: Variant) -> Immediate {
+        let wins = |node: &OuterBoard, mover: Mark| {
+            node.overall_winner
+                .is_some_and(|line_owner| variant.winner(line_owner) == mover)
+        };
+        let children: ArrayVec<(Move, OuterBoard), MAX_MOVES> = board
+            .moves(player)
+            .filter_map(|r#move| Some((r#move, board.make_move(r#move)?)))
+            .collect();
+        if let Some(&(r#move, _)) = children.iter().find(|(_, child)| wins(child, player)) {
+            return Immediate::Move(r#move, Shortcut::Win);
+        }
+        if let [(r#move, _)] = children[..] {
+            return Immediate::Move(r#move, Shortcut::OnlyMove);
+        }
+
+        let safe: Vec<Move> = children
+            .iter()
+            .filter(|(_, child)| {
+                !wins(child, !player)
+                    && !child.moves(!player).any(|reply| {
+                        child
+                            .make_move(reply)
+                            .is_some_and(|grandchild| wins(&grandchild, !player))
+                    })
+            })
+            .map(|&(r#move, _)| r#move)
+            .collect();
+        match safe.len() {
+            0 => Immediate::Nothing,
+            1 => Immediate::Move(safe[0], Shortcut::OnlyDefense),
+            n if n < children.len() => Immediate::Safe(safe),
+            _ => Immediate::Nothing,
+        }
+    }
+
+    // one ply and no heuristic, so it's cheap enough to run for every hovered cell; a won
+    // subboard is reported before a double threat
+    pub fn trap(board: &OuterBoard, player: Mark) -> Option<Trap> {
+        let mut double_threat = None;
+        for r#move in board.moves(player) {
+            let Some(child) = board.make_move(r#move) else {
+                continue;
+            };
+            let (r, c) = (r#move.outer.0 as usize, r#move.outer.1 as usize);
+            let (before, after) = (&board.boards[r][c], &child.boards[r][c]);
+            if before.winner.is_none() && after.winner == Some(player) {
+                return Some(Trap::Subboard(r#move));
+            }
+            if double_threat.is_none()
+                && after.winner.is_none()
+                && before.threats(player) < 2
+                && after.threats(player) >= 2
+            {
+                double_threat = Some(Trap::DoubleThreat(r#move));
+            }
+        }
+        double_threat
+    }
+
+    // a guess at how good `move` is for whoever plays it, only to search the likely best
+    // moves first so that the rest get cut off: taking the subboard, then the center of one,
+    // while sending the opponent where they can take one or choose freely comes last
+    fn order_score(node: &OuterBoard, r#move: Move) -> i32 {
+        let (outer, inner) = (r#move.outer, r#move.inner);
+        let mut squares = node.boards[outer.0 as usize][outer.1 as usize].squares;
+        squares[inner.0 as usize][inner.1 as usize] = Some(r#move.player);
+        let mut score = 0;
+        if lines(&squares).any(|line| line.iter().all(|&cell| cell == Some(r#move.player))) {
+            score += 4;
+        }
+        if inner == (1, 1) {
+            score += 1;
+        }
+        match node.next_active_square(inner) {
+            Some((r, c)) if node.boards[r as usize][c as usize].threats(!r#move.player) > 0 => {
+                score -= 3
+            }
+            Some(_) => {}
+            None => score -= 2,
+        }
+        score
+    }
+
+    // with a limit, keeps the moves whose child scores best for `mover` on the heuristic alone;
+    // without one, every move in `order_score` order
+    fn candidate_moves(&self, node: &OuterBoard, mover: Mark) -> ArrayVec<Move, MAX_MOVES> {
+        let mut moves = node.possible_moves(mover);
+        let Some(limit) = self.options.max_moves_considered else {
+            if !self.options.generation_order {
+                // stable, so equal scores keep the generation order
+                moves.sort_by_key(|&r#move| std::cmp::Reverse(Self::order_score(node, r#move)));
+            }
+            return moves;
+        };
+        if moves.len() <= limit {
+            return moves;
+        }
+        let mut scored: ArrayVec<(Move, i32), MAX_MOVES> = moves
+            .iter()
             .map(|&r#move| {
-                let value = board.make_move(r#move).map_or(i32::MIN, |child| {
-                    searcher.branch(&child, MAX_DEPTH - 1, false, i32::MIN, i32::MAX)
+                let score = node.make_move(r#move).map_or(i32::MIN, |child| {
+                    // the contempt is the searching side's, so a draw is worth the opposite
+                    // to the other one
+                    let draw_score = if mover == self.player {
+                        self.options.draw_score()
+                    } else {
+                        self.options.draw_score().saturating_neg()
+                    };
+                    Self::heuristic(
+                        &child,
+                        mover,
+                        !mover,
+                        self.options.variant,
+                        &self.options.weights,
+                        draw_score,
+                    )
                 });
-                debug!("move" = ?r#move, "value" = value, "computer_move_opportunity");
-                (r#move, value)
+                (r#move, score)
             })
-            .max_by_key(|&(_, value)| value)
+            .collect();
+        // stable, so equal scores keep the generation order
+        scored.sort_by_key(|&(_, score)| std::cmp::Reverse(score));
+        moves.clear();
+        moves.extend(scored.into_iter().take(limit).map(|(r#move, _)| r#move));
+        moves
+    }
+
+    fn out_of_time(&self) -> bool {
+        let cancelled = self
+            .cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+        let out = cancelled
+            || !self.deterministic
+                && std::time::Instant::now().saturating_duration_since(self.start_time)
+                    > self.max_time;
+        if out {
+            self.timed_out.store(true, Ordering::Relaxed);
+        }
+        out
     }
 
     fn branch(
@@ -59,38 +872,71 @@ impl Searcher {
         node: &OuterBoard,
         depth: usize,
         maximizing: bool,
-
-        mut alpha: i32,
-        mut beta: i32,
+        alpha: i32,
+        beta: i32,
     ) -> i32 {
+        self.nodes.fetch_add(1, Ordering::Relaxed);
+        self.deepest
+            .fetch_max(self.max_depth - depth, Ordering::Relaxed);
         let table = TRANSPOSITION_TABLE.get().unwrap();
-        let key = TTableKey {
-            board: *node,
-            maximizing,
-            player: self.player,
-        };
+        let key = TTableKey::new(node, maximizing, self.player, self.options_fingerprint);
         if let Some(cached) = table.get(&key)
             && let cached = cached.value()
-            && cached.depth >= depth
+            && cached.answers(depth, alpha, beta)
         {
             cached.hits.fetch_add(1, Ordering::Relaxed);
             return cached.eval;
         }
 
-        let eval = if !node
-            .boards
-            .iter()
-            .any(|row| row.iter().any(|b| b.can_play()))
-        {
-            0 // Draw
-        } else if depth == 0
-            || node.overall_winner.is_some()
-            || std::time::Instant::now()
-                .saturating_duration_since(self.start_time)
-                .as_secs_f64()
-                > MAX_SEARCH_TIME
-        {
-            Self::heuristic(
+        let eval = match Terminal::of(node, self.player, self.options.variant) {
+            Some(Terminal::Win) => i32::MAX,
+            Some(Terminal::Loss) => i32::MIN,
+            Some(Terminal::Draw) => self.options.draw_score(),
+            None => self.unfinished(node, depth, maximizing, alpha, beta),
+        };
+
+        // a branch cut short by the clock is worth nothing to later searches
+        if self.timed_out.load(Ordering::Relaxed) {
+            return eval;
+        }
+        let bound = if eval <= alpha {
+            Bound::Upper
+        } else if eval >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        // a deeper result already stored is worth more than this one, whatever its bound
+        let value = TTableValue {
+            eval,
+            depth,
+            bound,
+            hits: AtomicUsize::new(0),
+        };
+        match table.entry(key) {
+            dashmap::Entry::Occupied(mut entry) if entry.get().depth <= depth => {
+                entry.insert(value);
+            }
+            dashmap::Entry::Occupied(_) => {}
+            dashmap::Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+
+        eval
+    }
+
+    // a game still going: the heuristic at the depth cutoff, otherwise one more ply of search
+    fn unfinished(
+        &self,
+        node: &OuterBoard,
+        depth: usize,
+        maximizing: bool,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        if depth == 0 || self.out_of_time() {
+            return Self::heuristic(
                 node,
                 self.player,
                 if maximizing {
@@ -98,11 +944,15 @@ impl Searcher {
                 } else {
                     !self.player
                 },
-            )
-        } else if maximizing {
+                self.options.variant,
+                &self.options.weights,
+                self.options.draw_score(),
+            );
+        }
+        if maximizing {
             let mut best_eval = i32::MIN;
 
-            for r#move in node.possible_moves(self.player) {
+            for r#move in self.candidate_moves(node, self.player) {
                 let Some(child) = node.make_move(r#move) else {
                     continue;
                 };
@@ -117,7 +967,7 @@ impl Searcher {
             best_eval
         } else {
             let mut best_eval = i32::MAX;
-            for r#move in node.possible_moves(!self.player) {
+            for r#move in self.candidate_moves(node, !self.player) {
                 let Some(child) = node.make_move(r#move) else {
                     continue;
                 };
@@ -129,152 +979,924 @@ impl Searcher {
                 }
             }
             best_eval
-        };
-
-        for node in node.all_variations() {
-            table.insert(
-                TTableKey {
-                    board: node,
-                    maximizing,
-                    player: self.player,
-                },
-                TTableValue {
-                    eval,
-                    depth,
-                    hits: AtomicUsize::new(0),
-                },
-            );
         }
+    }
 
-        eval
+    // `draw_score` is what a drawn game is worth to `player`, as `SearchOptions::draw_score`
+    pub fn heuristic<const N: usize>(
+        board: &OuterBoard<N>,
+        player: Mark,
+        next_mark: Mark,
+        variant: Variant,
+        weights: &HeuristicWeights,
+        draw_score: i32,
+    ) -> i32 {
+        Self::heuristic_breakdown(board, player, next_mark, variant, weights, draw_score).total()
     }
 
-    pub fn heuristic(board: &OuterBoard, player: Mark, next_mark: Mark) -> i32 {
-        let meta_board = board.meta_board();
+    pub fn heuristic_breakdown<const N: usize>(
+        board: &OuterBoard<N>,
+        player: Mark,
+        next_mark: Mark,
+        variant: Variant,
+        weights: &HeuristicWeights,
+        draw_score: i32,
+    ) -> HeuristicBreakdown {
+        let mut breakdown = HeuristicBreakdown {
+            clamp: weights.eval_clamp,
+            ..Default::default()
+        };
 
         // Immediate win/loss
-        if let Some(winner) = meta_board.winner {
-            return if winner == player { i32::MAX } else { i32::MIN };
+        if let Some(line_owner) = board.overall_winner {
+            let winner = variant.winner(line_owner);
+            breakdown.terminal = Some(if winner == player { i32::MAX } else { i32::MIN });
+            return breakdown;
         }
 
-        let mut score = 0;
+        // dead drawn, or with nowhere left to play: nothing further can score
+        if board.is_over() {
+            breakdown.terminal = Some(draw_score);
+            return breakdown;
+        }
+
+        // with no line of subboards left for either side it's as drawn as a finished game; with
+        // none left for one side only, that side can't win any more
+        match (board.can_still_win(player), board.can_still_win(!player)) {
+            (false, false) => {
+                breakdown.terminal = Some(draw_score);
+                return breakdown;
+            }
+            (true, false) => breakdown.unwinnable = weights.meta_unwinnable,
+            (false, true) => breakdown.unwinnable = weights.meta_unwinnable.saturating_neg(),
+            (true, true) => {}
+        }
+
+        // the meta board is built once and shared by every meta term
+        let meta_board_with_draws = board.meta_board_with_draws();
+        let meta_squares =
+            meta_board_with_draws.map(|row| row.map(|cell| cell.and_then(Result::ok)));
+
+        // Meta board is more important; everything saturates, the weights can be anything
+        breakdown.meta_control =
+            weights
+                .meta_multiplier
+                .saturating_mul(Self::control(&meta_squares, player, weights));
+        breakdown.meta_threats = weights
+            .meta_multiplier
+            .saturating_mul(weights.threat)
+            .saturating_mul(
+                threats(meta_board_with_draws, Ok(player)) as i32
+                    - threats(meta_board_with_draws, Ok(!player)) as i32,
+            );
 
-        let score_inner = |score: &mut i32, inner_board: &InnerBoard, score_threats: bool| {
-            if let Some(winner) = inner_board.winner {
-                // Small board win/loss
-                if winner == player {
-                    *score += 1000;
+        for (r, row) in board.boards.iter().enumerate() {
+            for (c, inner_board) in row.iter().enumerate() {
+                let importance = match CellKind::of::<N>(r, c) {
+                    CellKind::Center => weights.center_subboard,
+                    CellKind::Corner => weights.corner_subboard,
+                    CellKind::Edge => weights.edge_subboard,
+                };
+                let scaled = |score: i32| score.saturating_mul(importance) / 100;
+                if let Some(winner) = inner_board.winner {
+                    // Small board win/loss
+                    let win = if winner == player {
+                        weights.subboard_win
+                    } else {
+                        weights.subboard_win.saturating_neg()
+                    };
+                    breakdown.subboard_wins = breakdown.subboard_wins.saturating_add(scaled(win));
                 } else {
-                    *score -= 1000;
+                    breakdown.threats =
+                        breakdown
+                            .threats
+                            .saturating_add(scaled(weights.threat.saturating_mul(
+                                inner_board.threats(player) as i32
+                                    - inner_board.threats(!player) as i32,
+                            )));
+                    let control = |kind, weight| {
+                        scaled(Self::cells_control(
+                            &inner_board.squares,
+                            player,
+                            kind,
+                            weight,
+                        ))
+                    };
+                    breakdown.center = breakdown
+                        .center
+                        .saturating_add(control(CellKind::Center, weights.center));
+                    breakdown.edges = breakdown
+                        .edges
+                        .saturating_add(control(CellKind::Edge, weights.edge));
+                    breakdown.corners = breakdown
+                        .corners
+                        .saturating_add(control(CellKind::Corner, weights.corner));
                 }
+            }
+        }
+
+        if board.active_square.is_none() {
+            breakdown.free_choice = if next_mark == player {
+                weights.free_choice // Favorable position when we can choose any board
             } else {
-                // Threats
-                if score_threats {
-                    *score += 100 * Self::threats(inner_board.squares, player) as i32;
-                    *score -= 100 * Self::threats(inner_board.squares, !player) as i32;
-                }
+                weights.free_choice.saturating_neg() // Unfavorable position when opponent can choose any board
+            };
+        }
 
-                // Center control
-                if inner_board.squares[1][1] == Some(player) {
-                    *score += 10;
-                } else if inner_board.squares[1][1] == Some(!player) {
-                    *score -= 10;
-                }
+        match variant {
+            Variant::Standard => breakdown,
+            Variant::Misere => breakdown.inverted(),
+        }
+    }
 
-                // Edge control
-                for &(r, c) in &[(0, 1), (1, 0), (1, 2), (2, 1)] {
-                    if inner_board.squares[r][c] == Some(player) {
-                        *score += 5;
-                    } else if inner_board.squares[r][c] == Some(!player) {
-                        *score -= 5;
-                    }
+    fn control<const N: usize>(
+        squares: &[[Option<Mark>; N]; N],
+        player: Mark,
+        weights: &HeuristicWeights,
+    ) -> i32 {
+        Self::cells_control(squares, player, CellKind::Center, weights.center)
+            .saturating_add(Self::cells_control(
+                squares,
+                player,
+                CellKind::Edge,
+                weights.edge,
+            ))
+            .saturating_add(Self::cells_control(
+                squares,
+                player,
+                CellKind::Corner,
+                weights.corner,
+            ))
+    }
+
+    fn cells_control<const N: usize>(
+        squares: &[[Option<Mark>; N]; N],
+        player: Mark,
+        kind: CellKind,
+        weight: i32,
+    ) -> i32 {
+        let mut score: i32 = 0;
+        for (r, row) in squares.iter().enumerate() {
+            for (c, &cell) in row.iter().enumerate() {
+                if CellKind::of::<N>(r, c) != kind {
+                    continue;
+                }
+                match cell {
+                    Some(mark) if mark == player => score = score.saturating_add(weight),
+                    Some(_) => score = score.saturating_sub(weight),
+                    None => {}
                 }
+            }
+        }
+        score
+    }
+}
 
-                // Corner control
-                for &(r, c) in &[(0, 0), (0, 2), (2, 0), (2, 2)] {
-                    if inner_board.squares[r][c] == Some(player) {
-                        *score += 2;
-                    } else if inner_board.squares[r][c] == Some(!player) {
-                        *score -= 2;
-                    }
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::super::tests::positions;
+    use super::*;
+
+    // the table and the resource limits are shared by every search in the process, so tests that
+    // count nodes or compare answers take turns, each starting from an empty table
+    static TABLE: Mutex<()> = Mutex::new(());
+
+    fn cold_table() -> std::sync::MutexGuard<'static, ()> {
+        let guard = TABLE
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        clear_transposition_table();
+        guard
+    }
+
+    // X holds the top left and top middle subboards and is sent to the top right one, where
+    // the top row is a cell away from done: taking it completes the line of subboards
+    fn top_row_within_reach() -> OuterBoard {
+        let mut board = OuterBoard::default();
+        let mut put = |(outer_row, outer_col): (usize, usize), cells: &[(usize, usize)], mark| {
+            for &(row, col) in cells {
+                board.boards[outer_row][outer_col].squares[row][col] = Some(mark);
+            }
+        };
+        put((0, 0), &[(0, 0), (0, 1), (0, 2)], Mark::X);
+        put((0, 1), &[(0, 0), (0, 1), (0, 2)], Mark::X);
+        put((0, 2), &[(0, 0), (0, 1)], Mark::X);
+        put((1, 0), &[(1, 0), (1, 1), (2, 2)], Mark::O);
+        put((1, 1), &[(1, 0), (2, 1), (2, 2)], Mark::O);
+        put((2, 0), &[(0, 1), (2, 2)], Mark::O);
+        board.active_square = Some((0, 2));
+        board.recompute_winners();
+        board
+    }
+
+    #[test]
+    fn dead_draw_scores_as_a_draw() {
+        let mut board = OuterBoard::default();
+        let (x, o) = (Some(Mark::X), Some(Mark::O));
+        let winners = [[x, x, o], [o, o, x], [x, None, None]];
+        for (row, marks) in winners.iter().enumerate() {
+            for (col, &winner) in marks.iter().enumerate() {
+                if let Some(mark) = winner {
+                    board.boards[row][col].squares[0] = [Some(mark); 3];
                 }
             }
+        }
+        // only the bottom row is left, and O has a mark on every line of its last subboard
+        for (row, col) in [(0, 0), (1, 1), (1, 2), (2, 1)] {
+            board.boards[2][2].squares[row][col] = Some(Mark::O);
+        }
+        board.recompute_winners();
+        assert!(!board.is_over());
+
+        let options = SearchOptions {
+            contempt: 300,
+            ..Default::default()
         };
+        let eval = |player| {
+            Searcher::heuristic(
+                &board,
+                player,
+                Mark::X,
+                Variant::Standard,
+                &options.weights,
+                options.draw_score(),
+            )
+        };
+        assert_eq!(eval(Mark::X), options.draw_score());
+        assert_eq!(eval(Mark::O), options.draw_score());
+    }
 
-        score_inner(&mut score, &meta_board, false);
+    #[test]
+    fn misere_avoids_the_line_standard_takes() {
+        let board = top_row_within_reach();
+        assert_eq!(board.validate(), Ok(()));
+        let winning = Move {
+            outer: (0, 2),
+            inner: (0, 2),
+            player: Mark::X,
+        };
+        assert_eq!(
+            board.make_move(winning).unwrap().overall_winner,
+            Some(Mark::X)
+        );
 
-        let meta_board_with_draws = board.meta_board_with_draws();
-        score += 100 * Self::threats(meta_board_with_draws, Ok(player)) as i32;
-        score -= 100 * Self::threats(meta_board_with_draws, Ok(!player)) as i32;
-        score *= 5; // Meta board is more important
-
-        for row in 0..3 {
-            for col in 0..3 {
-                let inner_board = &board.boards[row][col];
-                score_inner(&mut score, inner_board, true);
+        let _table = cold_table();
+        for variant in [Variant::Standard, Variant::Misere] {
+            clear_transposition_table();
+            let options = SearchOptions {
+                variant,
+                ..Default::default()
+            };
+            let (best, _) = Searcher::search_deterministic(&board, Mark::X, 3, options);
+            let (r#move, _) = best.expect("X has moves");
+            match variant {
+                Variant::Standard => assert_eq!(r#move, winning),
+                Variant::Misere => assert_ne!(r#move, winning),
             }
         }
+    }
+
+    // the default time limit, stopping at `max_depth`
+    fn to_depth(max_depth: usize) -> SearchConfig {
+        SearchConfig {
+            max_depth,
+            ..Default::default()
+        }
+    }
 
-        if board.active_square.is_none() {
-            if next_mark == player {
-                score += 200; // Favorable position when we can choose any board
-            } else {
-                score -= 200; // Unfavorable position when opponent can choose any board
+    fn positions_in_play(count: usize) -> impl Iterator<Item = (OuterBoard, Mark)> {
+        positions(count)
+            .into_iter()
+            .filter(|(board, _)| !board.is_over())
+    }
+
+    // X has the top left and top middle subboards and two in a row in the top right one, every
+    // other subboard is full, and play is in the top right: whoever moves, there's one move to
+    // make
+    fn shortcut_board() -> OuterBoard {
+        use Mark::{O, X};
+        let mut board = OuterBoard::default();
+        for r in 0..3 {
+            board.boards[0][0].squares[r][0] = Some(X);
+            board.boards[0][1].squares[r][1] = Some(X);
+        }
+        board.boards[0][2].squares[0][0] = Some(X);
+        board.boards[0][2].squares[0][1] = Some(X);
+        let drawn = [[X, O, X], [X, O, O], [O, X, X]];
+        for row in &mut board.boards[1..] {
+            for inner_board in row {
+                inner_board.squares = drawn.map(|row| row.map(Some));
             }
         }
+        board.recompute_winners();
+        board.active_square = Some((0, 2));
+        board
+    }
 
-        score
+    #[test]
+    fn obvious_moves_skip_the_search() {
+        use Mark::{O, X};
+        let _table = cold_table();
+        let board = shortcut_board();
+        for (player, shortcut) in [(X, Shortcut::Win), (O, Shortcut::OnlyDefense)] {
+            clear_transposition_table();
+            let (found, stats) = Searcher::search(&board, player, to_depth(4), Default::default());
+            let (r#move, _) = found.expect("there's a move");
+            assert_eq!((r#move.outer, r#move.inner), ((0, 2), (0, 2)), "{player}");
+            assert_eq!(stats.shortcut, Some(shortcut), "{player}");
+            assert_eq!(stats.nodes, 0, "{player} searched for an obvious move");
+        }
+    }
+
+    // a lone legal move gets played without a search
+    #[test]
+    fn an_only_move_skips_the_search() {
+        use Mark::{O, X};
+        let _table = cold_table();
+        let mut board = shortcut_board();
+        board.boards[0][2].squares = [[X, O, X], [X, O, O], [O, X, X]].map(|row| row.map(Some));
+        board.boards[0][2].squares[1][1] = None;
+        board.recompute_winners();
+        board.active_square = Some((0, 2));
+        let only = board.possible_moves(O)[0];
+        let (found, stats) = Searcher::search(&board, O, to_depth(4), Default::default());
+        assert_eq!(found.map(|(r#move, _)| r#move), Some(only));
+        assert_eq!(stats.nodes, 0);
+        assert_eq!(stats.shortcut, Some(Shortcut::OnlyMove));
     }
 
-    fn threats<T: Eq + Copy>(squares: [[Option<T>; 3]; 3], mark: T) -> usize {
-        let mut threats = 0;
+    // the top right subboard of `shortcut_board` filled up but for its centre, where O can't
+    // win. X has two moves, each settling the game within two plies: one into the top right
+    // subboard, which `threatening` completes a column with if it wins it, and one into the top
+    // left, which fills it and sends the opponent to the top right
+    fn draw_choice_board(threatening: Mark) -> OuterBoard {
+        use Mark::{O, X};
+        let t = threatening;
+        let won = |mark| [[Some(mark); 3], [None; 3], [None; 3]];
+        let mut board = OuterBoard::default();
+        let drawn = [[X, O, X], [X, O, O], [O, X, X]].map(|row| row.map(Some));
+        // one cell short of full; only `t` completes a line in it
+        let one_left = |t: Mark| {
+            [
+                [Some(t), Some(t), None],
+                [Some(!t), Some(!t), Some(t)],
+                [Some(t), Some(!t), Some(!t)],
+            ]
+        };
+        board.boards[0][0].squares = one_left(O);
+        board.boards[0][1].squares = drawn;
+        board.boards[0][2].squares = one_left(t);
+        board.boards[1][0].squares = won(!t);
+        board.boards[1][1].squares = won(X);
+        board.boards[1][2].squares = won(t);
+        board.boards[2][0].squares = won(O);
+        board.boards[2][1].squares = drawn;
+        board.boards[2][2].squares = won(t);
+        board.recompute_winners();
+        board
+    }
 
-        for row in 0..3 {
-            if squares[row]
+    // a forced draw scores minus the contempt, below a forced win and above a forced loss
+    #[test]
+    fn forced_draws_score_the_contempt() {
+        use Mark::{O, X};
+        let _table = cold_table();
+        for contempt in [0, 300, -300] {
+            let options = SearchOptions {
+                contempt,
+                ..Default::default()
+            };
+            for (threatening, draw_outer, other) in [(O, (0, 2), i32::MIN), (X, (0, 0), i32::MAX)] {
+                let board = draw_choice_board(threatening);
+                clear_transposition_table();
+                let (results, _) =
+                    Searcher::search_multipv(&board, X, to_depth(4), options, usize::MAX);
+                let value_of = |outer| {
+                    results
+                        .iter()
+                        .find(|(r#move, _)| r#move.outer == outer)
+                        .map(|&(_, value)| value)
+                };
+                let other_outer = if draw_outer == (0, 2) { (0, 0) } else { (0, 2) };
+                assert_eq!(results.len(), 2);
+                assert_eq!(
+                    value_of(draw_outer),
+                    Some(options.draw_score()),
+                    "{contempt}"
+                );
+                assert_eq!(value_of(other_outer), Some(other), "{contempt}");
+                let expected = if other == i32::MAX {
+                    other_outer
+                } else {
+                    draw_outer
+                };
+                assert_eq!(results[0].0.outer, expected, "contempt {contempt}");
+            }
+        }
+    }
+
+    // with a single candidate per node the search can only ever play the statically best move
+    #[test]
+    fn pruning_to_one_move_plays_the_statically_best() {
+        let _table = cold_table();
+        for (board, to_move) in positions_in_play(400).step_by(20) {
+            let static_best = board
+                .possible_moves(to_move)
                 .iter()
-                .filter(|&&cell| cell == Some(mark))
-                .count()
-                == 2
-                && squares[row].iter().any(|&cell| cell.is_none())
-            {
-                threats += 1;
+                .filter_map(|&r#move| {
+                    let child = board.make_move(r#move)?;
+                    let score = Searcher::heuristic(
+                        &child,
+                        to_move,
+                        !to_move,
+                        Variant::Standard,
+                        &Default::default(),
+                        0,
+                    );
+                    Some((r#move, score))
+                })
+                .rev()
+                .max_by_key(|&(_, score)| score)
+                .map(|(r#move, _)| r#move);
+            clear_transposition_table();
+            let options = SearchOptions {
+                max_moves_considered: Some(1),
+                ..Default::default()
+            };
+            let (pruned, _) = Searcher::search_deterministic(&board, to_move, 4, options);
+            assert_eq!(pruned.map(|(r#move, _)| r#move), static_best, "on\n{board}");
+        }
+    }
+
+    // every personality has to come up with a legal move, and not all of them the same one
+    #[test]
+    fn personalities_play_legal_and_different_moves() {
+        let _table = cold_table();
+        let mut disagreements = 0;
+        for (board, to_move) in positions_in_play(1000).step_by(25) {
+            let chosen: Vec<_> = Personality::ALL
+                .iter()
+                .map(|personality| {
+                    clear_transposition_table();
+                    let options = SearchOptions {
+                        weights: personality.weights(),
+                        ..Default::default()
+                    };
+                    let (best, _) = Searcher::search_deterministic(&board, to_move, 2, options);
+                    let (r#move, _) = best.expect("a move");
+                    assert!(board.make_move(r#move).is_some(), "{personality}: {move}");
+                    r#move
+                })
+                .collect();
+            disagreements += chosen.iter().any(|&m| m != chosen[0]) as usize;
+        }
+        assert!(disagreements > 0, "every personality played the same moves");
+    }
+
+    // random positions are too early for won subboards, so this plays games far enough that the
+    // meta board has something on it; there the multiplier has to change some move
+    #[test]
+    fn the_meta_multiplier_changes_moves() {
+        let _table = cold_table();
+        let best = |board: &OuterBoard, to_move, meta_multiplier| {
+            clear_transposition_table();
+            let options = SearchOptions {
+                weights: HeuristicWeights {
+                    meta_multiplier,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            Searcher::search_deterministic(board, to_move, 2, options)
+                .0
+                .map(|(r#move, _)| r#move)
+        };
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut differing = 0;
+        for _ in 0..100 {
+            let plies = rng.random_range(30..60);
+            let (board, to_move) = OuterBoard::random_playout(&mut rng, plies);
+            let any_won = board
+                .boards
+                .iter()
+                .flatten()
+                .any(|inner| inner.winner.is_some());
+            if board.is_over() || !any_won {
+                continue;
             }
+            differing += (best(&board, to_move, 0) != best(&board, to_move, 5)) as usize;
         }
+        assert!(differing > 0);
+    }
 
-        for col in 0..3 {
-            if (0..3)
-                .map(|row| squares[row][col])
-                .filter(|&cell| cell == Some(mark))
-                .count()
-                == 2
-                && (0..3).any(|row| squares[row][col].is_none())
-            {
-                threats += 1;
+    // a position is worth the same however it's turned or reflected, the opposite to the other
+    // side, and the same to either side with the marks swapped
+    #[test]
+    fn the_heuristic_respects_the_symmetries() {
+        let weights = HeuristicWeights::default();
+        for (board, to_move) in positions_in_play(500) {
+            let eval = |board: &OuterBoard, player, next_mark| {
+                Searcher::heuristic(board, player, next_mark, Variant::Standard, &weights, 0)
+            };
+            let value = eval(&board, to_move, to_move);
+            for (variation, symmetric) in board.all_variations().into_iter().enumerate() {
+                assert_eq!(
+                    eval(&symmetric, to_move, to_move),
+                    value,
+                    "variation {variation}"
+                );
             }
+            assert_eq!(eval(&board, !to_move, to_move), -value, "on\n{board}");
+            assert_eq!(
+                eval(&board.swap_marks(), !to_move, !to_move),
+                value,
+                "on\n{board}"
+            );
         }
+    }
 
-        if (squares[0][0] == Some(mark) && squares[1][1] == Some(mark) && squares[2][2].is_none())
-            || (squares[0][0].is_none()
-                && squares[1][1] == Some(mark)
-                && squares[2][2] == Some(mark))
-            || (squares[0][0] == Some(mark)
-                && squares[1][1].is_none()
-                && squares[2][2] == Some(mark))
-        {
-            threats += 1;
+    // the same mark is worth more in the center subboard than in a corner one, which beats an
+    // edge one, and from the empty board that's where the engine goes first
+    #[test]
+    fn subboards_matter_by_their_place() {
+        let _table = cold_table();
+        let weights = HeuristicWeights::default();
+        let score = |outer: (usize, usize), weights: &HeuristicWeights| {
+            let mut board = OuterBoard::<3>::default();
+            board.boards[outer.0][outer.1].squares[1][1] = Some(Mark::X);
+            board.active_square = Some((1, 1));
+            Searcher::heuristic(&board, Mark::X, Mark::O, Variant::Standard, weights, 0)
+        };
+        let (center, corner, edge) = (
+            score((1, 1), &weights),
+            score((0, 0), &weights),
+            score((0, 1), &weights),
+        );
+        assert!(center > corner && corner > edge, "{center} {corner} {edge}");
+        let flat = HeuristicWeights {
+            center_subboard: 100,
+            corner_subboard: 100,
+            edge_subboard: 100,
+            ..weights
+        };
+        assert_eq!(score((1, 1), &flat), score((0, 1), &flat));
+
+        let (best, _) =
+            Searcher::search_deterministic(&OuterBoard::default(), Mark::X, 1, Default::default());
+        assert_eq!(best.map(|(r#move, _)| r#move.outer), Some((1, 1)));
+    }
+
+    // absurd weights may saturate but not overflow, and no position that isn't over may reach
+    // the scores of won and lost games; with the real weights one more won subboard still
+    // counts
+    #[test]
+    fn the_eval_stays_within_the_clamp() {
+        let extreme = HeuristicWeights {
+            meta_multiplier: i32::MAX,
+            threat: i32::MAX,
+            subboard_win: i32::MAX,
+            center: i32::MAX,
+            edge: i32::MIN,
+            corner: i32::MAX,
+            free_choice: i32::MIN,
+            center_subboard: i32::MAX,
+            corner_subboard: i32::MIN,
+            edge_subboard: i32::MAX,
+            meta_unwinnable: i32::MIN,
+            eval_clamp: DEFAULT_EVAL_CLAMP,
+        };
+        for (board, to_move) in positions_in_play(500) {
+            for variant in [Variant::Standard, Variant::Misere] {
+                let eval = Searcher::heuristic(&board, to_move, to_move, variant, &extreme, 0);
+                assert!(eval.abs() <= DEFAULT_EVAL_CLAMP, "{eval} on\n{board}");
+            }
         }
 
-        if (squares[0][2] == Some(mark) && squares[1][1] == Some(mark) && squares[2][0].is_none())
-            || (squares[0][2].is_none()
-                && squares[1][1] == Some(mark)
-                && squares[2][0] == Some(mark))
-            || (squares[0][2] == Some(mark)
-                && squares[1][1].is_none()
-                && squares[2][0] == Some(mark))
-        {
-            threats += 1;
+        let board = shortcut_board();
+        let mut worse = board;
+        worse.boards[0][1] = Default::default();
+        worse.recompute_winners();
+        let eval = |board: &OuterBoard| {
+            Searcher::heuristic(
+                board,
+                Mark::X,
+                Mark::O,
+                Variant::Standard,
+                &Default::default(),
+                0,
+            )
+        };
+        assert!(eval(&worse) < eval(&board));
+    }
+
+    // subboards nobody can win any more, full or not, and ones O won that X can't use: X can't
+    // win the game but O still can, and once O can't either it's scored a draw before the board
+    // fills
+    #[test]
+    fn unwinnable_games_score_accordingly() {
+        use Mark::{O, X};
+        type Squares = [[Option<Mark>; 3]; 3];
+        // one cell left, but every line has both marks in it
+        let dead = [
+            [Some(X), Some(O), Some(X)],
+            [Some(X), Some(O), Some(O)],
+            [Some(O), Some(X), None],
+        ];
+        let won_by_o = [[Some(O); 3], [None; 3], [None; 3]];
+        let build = |layout: [[Option<Squares>; 3]; 3]| {
+            let mut board = OuterBoard::default();
+            for (r, row) in layout.iter().enumerate() {
+                for (c, squares) in row.iter().enumerate() {
+                    if let Some(squares) = squares {
+                        board.boards[r][c].squares = *squares;
+                    }
+                }
+            }
+            board.recompute_winners();
+            board.active_square = Some((1, 1));
+            board
+        };
+        let weights = HeuristicWeights::default();
+        let score = |board: &OuterBoard, player, variant, draw_score| {
+            Searcher::heuristic(board, player, player, variant, &weights, draw_score)
+        };
+
+        // O only needs the center for the middle row or column, every line X has is blocked
+        let (d, o) = (Some(dead), Some(won_by_o));
+        let shut_out = build([[d, o, d], [o, None, o], [d, o, d]]);
+        assert!(!shut_out.is_over());
+        assert!(!shut_out.can_still_win(X));
+        assert!(shut_out.can_still_win(O));
+        assert!(score(&shut_out, X, Variant::Standard, 0) <= -weights.meta_unwinnable / 2);
+        assert!(score(&shut_out, O, Variant::Standard, 0) >= weights.meta_unwinnable / 2);
+        // in misère whoever can't complete a line can't lose by one either
+        assert!(score(&shut_out, X, Variant::Misere, 0) >= weights.meta_unwinnable / 2);
+
+        let drawn = build([[d, d, d], [d, None, d], [d, d, d]]);
+        assert!(!drawn.is_over());
+        assert!(!drawn.can_still_win(X) && !drawn.can_still_win(O));
+        for draw_score in [0, -300] {
+            assert_eq!(score(&drawn, X, Variant::Standard, draw_score), draw_score);
+            assert_eq!(score(&drawn, O, Variant::Standard, draw_score), draw_score);
+        }
+        let empty = OuterBoard::<3>::default();
+        assert!(empty.can_still_win(X) && empty.can_still_win(O));
+    }
+
+    // O to move finds nothing, X can make two threats at once, and with one more mark win
+    // outright
+    #[test]
+    fn traps_are_spotted() {
+        use Mark::{O, X};
+        let mut board = OuterBoard::default();
+        board.boards[0][0].squares[0][1] = Some(X);
+        board.boards[0][0].squares[1][0] = Some(X);
+        board.recompute_winners();
+        board.active_square = Some((0, 0));
+        assert_eq!(Searcher::trap(&board, O), None);
+        match Searcher::trap(&board, X) {
+            Some(Trap::DoubleThreat(r#move)) if r#move.outer == (0, 0) => {}
+            trap => panic!("expected a double threat for X, got {trap:?}"),
         }
+        board.boards[0][0].squares[0][0] = Some(X);
+        board.recompute_winners();
+        match Searcher::trap(&board, X) {
+            Some(Trap::Subboard(r#move)) if board.make_move(r#move).is_some() => {}
+            trap => panic!("expected X to win the subboard, got {trap:?}"),
+        }
+    }
+
+    // in the opening the table gives every rotation and reflection of a position one key, along
+    // with the position with the marks swapped and scored for the other side; later on each
+    // position keys on its own hash
+    #[test]
+    fn symmetric_positions_share_a_table_key() {
+        let options = SearchOptions::default().fingerprint();
+        let mut rng = StdRng::seed_from_u64(21);
+        for _ in 0..200 {
+            let plies = rng.random_range(0..=CANONICAL_KEY_MARKS);
+            let (board, to_move) = OuterBoard::random_playout(&mut rng, plies);
+            let key = TTableKey::new(&board, true, to_move, options);
+            for (variation, symmetric) in board.all_variations().into_iter().enumerate() {
+                let symmetric_key = TTableKey::new(&symmetric, true, to_move, options);
+                assert!(symmetric_key == key, "variation {variation} of\n{board}");
+            }
+            // a board that is its own swap, up to symmetry, keeps its marks for either side
+            let own_swap = board.swap_marks().canonical().0 == board.canonical().0;
+            let swapped_key = TTableKey::new(&board.swap_marks(), true, !to_move, options);
+            assert!(own_swap || swapped_key == key, "swapped marks of\n{board}");
+        }
+        let (late, to_move) = OuterBoard::random_playout(&mut rng, CANONICAL_KEY_MARKS + 10);
+        assert_eq!(
+            TTableKey::new(&late, true, to_move, options).board,
+            late.zobrist()
+        );
+    }
 
-        threats
+    // searching the same position again finds most of it in the table, and the same answer
+    #[test]
+    fn the_table_answers_a_repeated_search() {
+        let _table = cold_table();
+        for (board, to_move) in positions_in_play(100).take(10) {
+            clear_transposition_table();
+            let (first, first_stats) =
+                Searcher::search_deterministic(&board, to_move, 4, Default::default());
+            let (second, second_stats) =
+                Searcher::search_deterministic(&board, to_move, 4, Default::default());
+            assert_eq!(first, second);
+            assert!(
+                second_stats.nodes < first_stats.nodes || first_stats.nodes <= 1,
+                "{} nodes again, {} cold",
+                second_stats.nodes,
+                first_stats.nodes
+            );
+        }
+    }
+
+    // with one thread the parallel search has to find exactly what the sequential one does
+    #[test]
+    fn one_thread_searches_like_the_sequential_search() {
+        let _table = cold_table();
+        let limits = resource_limits();
+        set_resource_limits(ResourceLimits {
+            threads: Some(1),
+            ..limits
+        });
+        for (board, to_move) in positions_in_play(100).take(20) {
+            clear_transposition_table();
+            let (expected, _) =
+                Searcher::search_deterministic(&board, to_move, 3, Default::default());
+            clear_transposition_table();
+            let (found, _) = Searcher::search(&board, to_move, to_depth(3), Default::default());
+            assert_eq!(found, expected, "on\n{board}");
+        }
+        set_resource_limits(limits);
+    }
+
+    // with next to no time only depth 1 finishes, and that's still enough to take a win or to
+    // answer with a legal move
+    #[test]
+    fn no_time_still_gives_a_move() {
+        let _table = cold_table();
+        let no_time = SearchConfig {
+            max_time: std::time::Duration::from_nanos(1),
+            ..Default::default()
+        };
+        let board = shortcut_board();
+        // two lines at once, so the shortcut doesn't answer before any search
+        for count in [1, 2] {
+            clear_transposition_table();
+            let (moves, stats) = Searcher::search_multipv(
+                &board,
+                Mark::X,
+                no_time.clone(),
+                Default::default(),
+                count,
+            );
+            let won = moves
+                .first()
+                .and_then(|&(r#move, _)| board.make_move(r#move))
+                .is_some_and(|child| child.overall_winner == Some(Mark::X));
+            assert!(won && stats.depth > 0, "with {count} lines: {moves:?}");
+        }
+        for (board, to_move) in positions_in_play(100).take(20) {
+            clear_transposition_table();
+            let (moves, stats) =
+                Searcher::search_multipv(&board, to_move, no_time.clone(), Default::default(), 1);
+            let &(r#move, _) = moves.first().expect("a move within the time limit");
+            assert!(board.make_move(r#move).is_some());
+            assert!(
+                stats.depth > 0,
+                "the move didn't come from a finished depth"
+            );
+        }
+    }
+
+    // with no time limit a search stops exactly where its config says, with the same move the
+    // sequential search finds there, and the easy difficulty never goes past its depth
+    #[test]
+    fn searches_stop_at_their_depth() {
+        let _table = cold_table();
+        let unlimited = SearchConfig {
+            max_depth: 2,
+            max_time: std::time::Duration::MAX,
+            cancel: None,
+        };
+        for (board, to_move) in positions_in_play(100).take(20) {
+            clear_transposition_table();
+            let (expected, _) =
+                Searcher::search_deterministic(&board, to_move, 2, Default::default());
+            clear_transposition_table();
+            let (found, stats) =
+                Searcher::search(&board, to_move, unlimited.clone(), Default::default());
+            assert!(!stats.timed_out && stats.depth <= 2, "{stats:?}");
+            if stats.shortcut.is_none() {
+                assert_eq!(found, expected, "on\n{board}");
+            }
+        }
+        let easy = Difficulty::Easy.config();
+        let (board, to_move) = positions(1)[0];
+        clear_transposition_table();
+        let (_, stats) = Searcher::search(&board, to_move, easy.clone(), Default::default());
+        assert!(stats.depth <= easy.max_depth);
+    }
+
+    // searching the likely best moves first gets the same values with fewer nodes, and a move
+    // winning its subboard goes first
+    #[test]
+    fn move_ordering_saves_nodes() {
+        let _table = cold_table();
+        let unordered = SearchOptions {
+            generation_order: true,
+            ..Default::default()
+        };
+        let (mut ordered_nodes, mut unordered_nodes) = (0, 0);
+        for (board, to_move) in positions_in_play(100).take(20) {
+            clear_transposition_table();
+            let (ordered, stats) =
+                Searcher::search_deterministic(&board, to_move, 4, Default::default());
+            ordered_nodes += stats.nodes;
+            clear_transposition_table();
+            let (plain, stats) = Searcher::search_deterministic(&board, to_move, 4, unordered);
+            unordered_nodes += stats.nodes;
+            assert_eq!(
+                ordered.map(|(_, eval)| eval),
+                plain.map(|(_, eval)| eval),
+                "on\n{board}"
+            );
+        }
+        assert!(
+            ordered_nodes < unordered_nodes,
+            "{ordered_nodes} vs {unordered_nodes}"
+        );
+
+        let board = top_row_within_reach();
+        let first = board
+            .possible_moves(Mark::X)
+            .into_iter()
+            .max_by_key(|&r#move| Searcher::order_score(&board, r#move));
+        assert_eq!(first.map(|r#move| r#move.inner), Some((0, 2)));
+    }
+
+    // a search with all the time in the world still stops soon after it's cancelled
+    #[test]
+    fn a_cancelled_search_stops() {
+        let _table = cold_table();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let config = SearchConfig {
+            max_time: std::time::Duration::MAX,
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        let start = std::time::Instant::now();
+        let search = std::thread::spawn(move || {
+            Searcher::search(&OuterBoard::default(), Mark::X, config, Default::default())
+        });
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        cancel.store(true, Ordering::Relaxed);
+        let (_, stats) = search.join().unwrap();
+        assert!(stats.timed_out);
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    }
+
+    // cancelled before it starts, a search still has depth 1 to answer with
+    #[test]
+    fn a_cancelled_search_still_answers() {
+        let _table = cold_table();
+        let config = SearchConfig {
+            cancel: Some(Arc::new(AtomicBool::new(true))),
+            ..Default::default()
+        };
+        let (best, stats) = Searcher::search_multipv(
+            &OuterBoard::default(),
+            Mark::X,
+            config,
+            Default::default(),
+            3,
+        );
+        assert_eq!(best.len(), 3);
+        assert_eq!(stats.depth, 1);
+        assert!(stats.timed_out);
+    }
+
+    // the expected line replays move by move from the root, with the players taking turns, and
+    // nothing follows the end of the game
+    #[test]
+    fn the_best_line_replays() {
+        let _table = cold_table();
+        for (board, to_move) in positions_in_play(100).take(30) {
+            clear_transposition_table();
+            let line = board.best_line(to_move, to_depth(3), Default::default());
+            assert!(!line.is_empty(), "no line on\n{board}");
+            let mut node = board;
+            for (ply, &r#move) in line.iter().enumerate() {
+                assert!(!node.is_over(), "{move} comes after the end");
+                let mover = if ply % 2 == 0 { to_move } else { !to_move };
+                assert_eq!(r#move.player, mover, "{move} out of turn");
+                node = node.make_move(r#move).expect("a legal move in the line");
+            }
+        }
     }
 }
@@ -0,0 +1,83 @@
+use std::io::{BufRead, Write};
+
+use color_eyre::eyre::Result;
+use tracing::error;
+
+use crate::game::{Move, searcher::SearchStats};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    session: u64,
+    game: u32,
+    timestamp_ms: u128,
+    r#move: Move,
+    eval: i32,
+    // only known for moves the engine picked
+    nodes: Option<usize>,
+    depth: Option<usize>,
+}
+
+// appends one JSON object per move to a file chosen in the settings
+pub struct GameLog {
+    session: u64,
+    game: u32,
+}
+
+fn now() -> std::time::Duration {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+impl GameLog {
+    pub fn new() -> Self {
+        Self {
+            session: now().as_secs(),
+            game: 0,
+        }
+    }
+
+    pub fn next_game(&mut self) {
+        self.game += 1;
+    }
+
+    pub fn record(&self, path: &str, r#move: Move, eval: i32, stats: Option<SearchStats>) {
+        let entry = Entry {
+            session: self.session,
+            game: self.game,
+            timestamp_ms: now().as_millis(),
+            r#move,
+            eval,
+            nodes: stats.map(|stats| stats.nodes),
+            depth: stats.map(|stats| stats.depth),
+        };
+        if let Err(e) = Self::append(path, &entry) {
+            error!(error = ?e, path, "game_log_write_error");
+        }
+    }
+
+    // how many times each of the 81 cells was played, by row and column on the whole board
+    pub fn cell_counts(path: &str) -> Result<[[u32; 9]; 9]> {
+        let mut counts = [[0; 9]; 9];
+        let file = std::io::BufReader::new(std::fs::File::open(path)?);
+        for line in file.lines() {
+            let entry: Entry = serde_json::from_str(&line?)?;
+            let r#move = entry.r#move;
+            let row = r#move.outer.0 as usize * 3 + r#move.inner.0 as usize;
+            let col = r#move.outer.1 as usize * 3 + r#move.inner.1 as usize;
+            counts[row][col] += 1;
+        }
+        Ok(counts)
+    }
+
+    fn append(path: &str, entry: &Entry) -> Result<()> {
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        file.write_all(&line)?;
+        Ok(())
+    }
+}
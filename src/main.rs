@@ -1,60 +1,1142 @@
 use color_eyre::eyre::{Result, eyre};
 use eframe::egui::{self, Rect};
-use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{Receiver, SyncSender, sync_channel},
+};
 use tracing::{error, info};
 
-mod game;
+mod bench;
+mod editor;
+mod export;
+use supertris::game;
+mod game_log;
+mod position_db;
+mod profile;
+mod reproducer;
+mod settings;
+mod strength_test;
+mod tutorial;
 
-#[derive(Clone, Copy, Default, bincode::Encode, bincode::Decode)]
+// why the game on the last state is over; saved with it, so a save says how it ended
+#[derive(Clone, Copy, PartialEq, Eq, Debug, bincode::Encode, bincode::Decode)]
+enum GameEnd {
+    Resignation(game::Mark),
+    Draw(DrawReason),
+    // the mark that completed a line of subboards, which in misère is the loser
+    Line(game::Mark),
+    // the mark whose clock ran out
+    Timeout(game::Mark),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, bincode::Encode, bincode::Decode)]
+enum DrawReason {
+    Agreed,
+    NoLinesLeft,
+}
+
+impl GameEnd {
+    fn describe(self, variant: game::Variant) -> String {
+        match (self, variant) {
+            (GameEnd::Resignation(mark), _) => format!("{mark} ha abbandonato"),
+            (GameEnd::Timeout(mark), _) => format!("{mark} ha finito il tempo"),
+            (GameEnd::Draw(DrawReason::Agreed), _) => "Patta d'accordo".to_string(),
+            (GameEnd::Draw(DrawReason::NoLinesLeft), _) => {
+                "Patta: nessun tris è più possibile".to_string()
+            }
+            (GameEnd::Line(mark), game::Variant::Standard) => format!("{mark} ha fatto tris"),
+            (GameEnd::Line(mark), game::Variant::Misere) => {
+                format!("{mark} ha fatto tris e perde")
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, bincode::Encode, bincode::Decode)]
 struct GameState {
     board: game::OuterBoard,
     last_player_move: Option<game::Move>,
     last_computer_move: Option<game::Move>,
     eval: i32,
+    end: Option<GameEnd>,
+    to_move: game::Mark,
+    variant: game::Variant,
+    // the mark the human plays this game with; the computer has the other one, and every eval
+    // is from its side
+    human: game::Mark,
+    // what X's and O's clocks had left once the state's last move landed, increment included,
+    // for undo and redo to put back; `None` off the clock and in the root, which starts from the
+    // initial time
+    clocks: Option<(std::time::Duration, std::time::Duration)>,
 }
 
 impl GameState {
-    fn root(board: game::OuterBoard) -> Self {
-        Self {
+    // X moves first, whoever plays it
+    fn root(board: game::OuterBoard, variant: game::Variant, human: game::Mark) -> Self {
+        let mut this = Self {
             board,
             last_player_move: None,
             last_computer_move: None,
             eval: 0,
+            end: None,
+            to_move: game::Mark::X,
+            variant,
+            human,
+            clocks: None,
+        };
+        this.settle_end();
+        this
+    }
+
+    fn computer(&self) -> game::Mark {
+        !self.human
+    }
+
+    // called whenever a move lands, so the state that ends the game records how
+    fn settle_end(&mut self) {
+        if self.end.is_some() {
+            return;
+        }
+        if let Some(line_owner) = self.board.overall_winner {
+            self.end = Some(GameEnd::Line(line_owner));
+        } else if self.board.is_over() {
+            self.end = Some(GameEnd::Draw(DrawReason::NoLinesLeft));
+        }
+    }
+
+    // the root of a new game on `board`, with the rules the settings ask for
+    fn new_game(board: game::OuterBoard, settings: &settings::AppSettings) -> Self {
+        Self::root(
+            game::OuterBoard {
+                ruleset: settings.ruleset,
+                ..board
+            },
+            settings.variant,
+            settings.human_mark,
+        )
+    }
+}
+
+// what "Salva" wrote before draws, rulesets and variants: the board had no draw flag, and the
+// state kept nothing past the eval
+#[derive(bincode::Encode, bincode::Decode)]
+struct LegacyBoard {
+    boards: [[game::InnerBoard; 3]; 3],
+    overall_winner: Option<game::Mark>,
+    active_square: Option<(u8, u8)>,
+}
+
+#[derive(bincode::Encode, bincode::Decode)]
+struct LegacyGameState {
+    board: LegacyBoard,
+    last_player_move: Option<game::Move>,
+    last_computer_move: Option<game::Move>,
+    eval: i32,
+}
+
+impl From<LegacyGameState> for GameState {
+    fn from(legacy: LegacyGameState) -> Self {
+        let mut board = game::OuterBoard {
+            boards: legacy.board.boards,
+            active_square: legacy.board.active_square,
+            ..Default::default()
+        };
+        // the draw flag wasn't saved, so every result gets worked out again
+        board.recompute_winners();
+        let mut this = Self::root(board, game::Variant::Standard, game::HUMAN_MARK);
+        this.last_player_move = legacy.last_player_move;
+        this.last_computer_move = legacy.last_computer_move;
+        this.eval = legacy.eval;
+        // the computer answered within the same state, so only a lone human move leaves it to move
+        if this.last_player_move.is_some() && this.last_computer_move.is_none() {
+            this.to_move = this.computer();
+        }
+        this
+    }
+}
+
+// version 1, from before the human could pick a mark: they always played X
+#[derive(bincode::Encode, bincode::Decode)]
+struct GameStateV1 {
+    board: game::OuterBoard,
+    last_player_move: Option<game::Move>,
+    last_computer_move: Option<game::Move>,
+    eval: i32,
+    end: Option<GameEnd>,
+    to_move: game::Mark,
+    variant: game::Variant,
+}
+
+impl From<GameStateV1> for GameState {
+    fn from(state: GameStateV1) -> Self {
+        Self {
+            board: state.board,
+            last_player_move: state.last_player_move,
+            last_computer_move: state.last_computer_move,
+            eval: state.eval,
+            end: state.end,
+            to_move: state.to_move,
+            variant: state.variant,
+            human: game::HUMAN_MARK,
+            clocks: None,
+        }
+    }
+}
+
+// versions 2 and 3, from before the clocks were kept in each state
+#[derive(bincode::Encode, bincode::Decode)]
+struct GameStateV3 {
+    board: game::OuterBoard,
+    last_player_move: Option<game::Move>,
+    last_computer_move: Option<game::Move>,
+    eval: i32,
+    end: Option<GameEnd>,
+    to_move: game::Mark,
+    variant: game::Variant,
+    human: game::Mark,
+}
+
+impl From<GameStateV3> for GameState {
+    fn from(state: GameStateV3) -> Self {
+        Self {
+            board: state.board,
+            last_player_move: state.last_player_move,
+            last_computer_move: state.last_computer_move,
+            eval: state.eval,
+            end: state.end,
+            to_move: state.to_move,
+            variant: state.variant,
+            human: state.human,
+            clocks: None,
+        }
+    }
+}
+
+// what every save since the format got versioned starts with, followed by `SAVE_VERSION`
+const SAVE_MAGIC: &[u8; 4] = b"SPTR";
+// bumped whenever `GameState` changes shape, or gains a value older builds can't read; saves
+// without the header count as version 0
+const SAVE_VERSION: u32 = 4;
+
+#[derive(Debug)]
+enum SaveError {
+    // written by a newer build, or by something that isn't supertris at all
+    Unsupported { version: u32 },
+    Corrupt(bincode::error::DecodeError),
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Unsupported { version } => write!(
+                f,
+                "formato non supportato: il salvataggio è alla versione {version}, questa \
+                 versione legge fino alla {SAVE_VERSION}"
+            ),
+            SaveError::Corrupt(e) => write!(f, "file corrotto: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+fn encode_save(states: &[GameState]) -> Vec<u8> {
+    let mut bytes = SAVE_MAGIC.to_vec();
+    bytes.extend_from_slice(&SAVE_VERSION.to_le_bytes());
+    bincode::encode_into_std_write(states, &mut bytes, bincode::config::standard())
+        .expect("writing into a vec can't fail");
+    bytes
+}
+
+// a versioned save, or failing that one of the formats from before the header; either has to
+// be read to the last byte
+fn decode_save(bytes: &[u8]) -> Result<Vec<GameState>, SaveError> {
+    let Some(rest) = bytes.strip_prefix(SAVE_MAGIC) else {
+        return decode_unversioned_save(bytes).map_err(SaveError::Corrupt);
+    };
+    let Some((version, body)) = rest.split_first_chunk::<4>() else {
+        return Err(SaveError::Corrupt(
+            bincode::error::DecodeError::UnexpectedEnd {
+                additional: 4 - rest.len(),
+            },
+        ));
+    };
+    match u32::from_le_bytes(*version) {
+        SAVE_VERSION => decode_exactly(body).map_err(SaveError::Corrupt),
+        // version 3 only added `GameEnd::Timeout`
+        2 | 3 => decode_exactly::<GameStateV3>(body)
+            .map(|states| states.into_iter().map(GameState::from).collect())
+            .map_err(SaveError::Corrupt),
+        1 => decode_exactly::<GameStateV1>(body)
+            .map(|states| states.into_iter().map(GameState::from).collect())
+            .map_err(SaveError::Corrupt),
+        version => Err(SaveError::Unsupported { version }),
+    }
+}
+
+// version 0: the version 1 states with no header, or failing that the older format
+fn decode_unversioned_save(bytes: &[u8]) -> Result<Vec<GameState>, bincode::error::DecodeError> {
+    match decode_exactly::<GameStateV1>(bytes) {
+        Ok(states) => Ok(states.into_iter().map(GameState::from).collect()),
+        Err(current) => match decode_exactly::<LegacyGameState>(bytes) {
+            Ok(legacy) => {
+                info!(states = legacy.len(), "legacy_save_decoded");
+                Ok(legacy.into_iter().map(GameState::from).collect())
+            }
+            Err(_) => Err(current),
+        },
+    }
+}
+
+fn decode_exactly<T: bincode::Decode<()>>(
+    bytes: &[u8],
+) -> Result<Vec<T>, bincode::error::DecodeError> {
+    match bincode::decode_from_slice(bytes, bincode::config::standard()) {
+        Ok((states, read)) if read == bytes.len() => Ok(states),
+        Ok(_) => Err(bincode::error::DecodeError::Other(
+            "dati in più dopo la partita",
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+// both sides played by the engine from the empty board until the game ends, with the states
+// laid out as the app keeps them
+#[cfg(test)]
+fn run_selfplay(
+    config_x: game::searcher::SearchConfig,
+    config_o: game::searcher::SearchConfig,
+) -> Vec<GameState> {
+    let variant = game::Variant::default();
+    let options = game::searcher::SearchOptions {
+        variant,
+        ..Default::default()
+    };
+    let mut states = vec![GameState::root(
+        game::OuterBoard::default(),
+        variant,
+        game::HUMAN_MARK,
+    )];
+    loop {
+        let state = *states.last().unwrap();
+        if state.end.is_some() {
+            break;
+        }
+        let config = match state.to_move {
+            game::Mark::X => config_x.clone(),
+            game::Mark::O => config_o.clone(),
+        };
+        let (Some((r#move, eval)), _) =
+            game::searcher::Searcher::search(&state.board, state.to_move, config, options)
+        else {
+            break;
+        };
+        let Some(board) = state.board.make_move(r#move) else {
+            error!("move" = %r#move, "selfplay_move_invalid");
+            break;
+        };
+        let eval = if r#move.player == state.computer() {
+            eval
+        } else {
+            eval.saturating_neg()
+        };
+        push_move(&mut states, r#move, board, eval);
+    }
+    states
+}
+
+// a human move starts a new state, and the computer's answer goes into the state it answers;
+// `eval` is from the computer's side
+fn push_move(states: &mut Vec<GameState>, r#move: game::Move, board: game::OuterBoard, eval: i32) {
+    if r#move.player == states.last().unwrap().computer() {
+        let state = states.last_mut().unwrap();
+        state.last_computer_move = Some(r#move);
+        state.to_move = !r#move.player;
+        state.board = board;
+        state.eval = eval;
+        state.settle_end();
+    } else {
+        let mut state = *states.last().unwrap();
+        state.last_player_move = Some(r#move);
+        state.board = board;
+        state.to_move = !r#move.player;
+        state.eval = eval;
+        state.settle_end();
+        states.push(state);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SearchKind {
+    Play,
+    Analyze,
+    // a silent full-strength analysis of every move, for the coach to judge the human's move
+    Coach,
+    // the human's most resilient move, whoever is ahead, with the line that follows it
+    Defense,
+    // one ply of "Analizza partita", every move scored so the played one can be judged
+    Review,
+    // the move the full-strength engine would play for the human, shown instead of played
+    Hint,
+}
+
+struct SearchRequest {
+    kind: SearchKind,
+    player: game::Mark,
+    board: game::OuterBoard,
+    // how far `SearchKind::Play` may look; the analyses always get the default
+    config: game::searcher::SearchConfig,
+    // set by `App::cancel_search` to stop the search, or by the worker once it answers, so
+    // that exactly one of them decides whether the answer gets sent
+    cancel: Arc<AtomicBool>,
+    options: game::searcher::SearchOptions,
+}
+
+const ANALYSIS_LINES: usize = 3;
+// how many random boards get drawn looking for a balanced one before settling for the last
+const BALANCED_RANDOM_ATTEMPTS: usize = 200;
+// how weak the coaching engine plays when no branching limit is set in the settings
+const COACHING_MOVES_CONSIDERED: usize = 3;
+// how often the side panel's clocks get redrawn while one of them runs
+const CLOCK_REPAINT_INTERVAL: std::time::Duration = std::time::Duration::from_millis(200);
+// how many more moves the computer budgets its remaining time for, with clocks on
+const CLOCK_MOVES_LEFT: u32 = 30;
+// the pause between a move landing and the next one being asked for in "Autopartita"
+const AUTOPLAY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(400);
+// how often the ui checks for a finished search when nothing else is repainting it
+const WORKER_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+#[derive(Clone)]
+struct Analysis {
+    board: game::OuterBoard,
+    player: game::Mark,
+    // best first, at most `ANALYSIS_LINES` of them
+    top: Vec<(game::Move, i32)>,
+    // the line expected after the best move, starting with it
+    pv: Vec<game::Move>,
+    stats: game::searcher::SearchStats,
+}
+
+impl Analysis {
+    fn best(&self) -> Option<(game::Move, i32)> {
+        self.top.first().copied()
+    }
+}
+
+enum SearchResponse {
+    Move(Option<(game::Move, i32)>, game::searcher::SearchStats),
+    Analysis(Analysis),
+    Coaching(Analysis),
+    Defense(Analysis),
+    Review(Analysis),
+    Hint(Option<game::Move>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MoveQuality {
+    Good,
+    Dubious,
+    Blunder,
+}
+
+// what "Analizza partita" found for one played move
+#[derive(Clone, Copy)]
+struct Annotation {
+    best: game::Move,
+    // both from the mover's point of view
+    best_eval: i32,
+    played_eval: i32,
+    quality: MoveQuality,
+}
+
+impl Annotation {
+    fn new(analysis: &Analysis, played: game::Move, blunder_threshold: i32) -> Option<Self> {
+        let (best, best_eval) = analysis.best()?;
+        let &(_, played_eval) = analysis.top.iter().find(|&&(m, _)| m == played)?;
+        let loss = best_eval as i64 - played_eval as i64;
+        let quality = if loss > blunder_threshold as i64 {
+            MoveQuality::Blunder
+        } else if loss > blunder_threshold as i64 / 4 {
+            MoveQuality::Dubious
+        } else {
+            MoveQuality::Good
+        };
+        Some(Self {
+            best,
+            best_eval,
+            played_eval,
+            quality,
+        })
+    }
+
+    fn symbol(&self) -> (&'static str, egui::Color32) {
+        match self.quality {
+            MoveQuality::Good => ("✓", egui::Color32::GREEN),
+            MoveQuality::Dubious => ("?!", egui::Color32::YELLOW),
+            MoveQuality::Blunder => ("??", egui::Color32::RED),
         }
     }
 }
 
+// a human move that lost a lot compared to the best one
+struct CoachingTip {
+    played: game::Move,
+    best: game::Move,
+    loss: i32,
+    revealed: bool,
+}
+
+#[derive(Clone, Copy)]
+struct PlayedMove {
+    // index of the state the move belongs to
+    state: usize,
+    board: game::OuterBoard,
+    r#move: game::Move,
+    // the computer's point of view, like `GameState::eval`
+    eval: i32,
+    annotation: Option<Annotation>,
+}
+
 struct App {
-    random_fill_percentage: f64,
+    settings: settings::AppSettings,
+    settings_open: bool,
+    tutorial_step: Option<usize>,
 
-    req_tx: SyncSender<(game::Mark, game::OuterBoard)>,
-    resp_rx: Receiver<Option<(game::Move, i32)>>,
+    req_tx: SyncSender<SearchRequest>,
+    resp_rx: Receiver<SearchResponse>,
     thinking: bool,
+    // the running search is a coaching one, which doesn't get the thinking modal
+    thinking_quietly: bool,
+    // the in-flight request's `SearchRequest::cancel`
+    cancel: Arc<AtomicBool>,
+    analysis: Option<Analysis>,
+    coaching_analysis: Option<Analysis>,
+    defense: Option<Analysis>,
+    coaching_tip: Option<CoachingTip>,
+    // the clocks this game is played with, and what's left on them
+    clock: Option<settings::ClockConfig>,
+    remaining_x: std::time::Duration,
+    remaining_o: std::time::Duration,
+    // "Suggerimento": the cell the engine would play for the human, until they move
+    hint: Option<game::Move>,
+
+    playing_for_me: bool,
+    // "Autopartita": the engine plays both sides, one move every `AUTOPLAY_INTERVAL`
+    autoplaying: bool,
+    // when the next autoplay move gets asked for, in egui time
+    autoplay_next: Option<f64>,
+    message: Option<String>,
+    // why "Carica" failed, shown in a modal until it's dismissed
+    load_error: Option<String>,
+    worker_crashed: bool,
+    zoomed: Option<(u8, u8)>,
 
+    // never empty. The first one is where the game started, with the computer's first move
+    // in it if the computer went first, so undoing never goes past it
     states: Vec<GameState>,
+    // what "Annulla mossa" took off `states`, latest last, with the moves that went with them,
+    // until a move is played over them
+    redo_stack: Vec<GameState>,
+    redo_moves: Vec<PlayedMove>,
+    // where the game started from, unless it was edited or loaded from a save
+    start: Option<reproducer::Start>,
+    game_moves: Vec<PlayedMove>,
+    // how many of `game_moves` already went into the position database
+    recorded_moves: usize,
+    position_db: Option<position_db::PositionDb>,
+    game_log: game_log::GameLog,
+    fullscreen_applied: Option<bool>,
+    editor: Option<editor::Editor>,
+    // counts read from the game log, dropped whenever a new move gets written there
+    logged_cell_counts: Option<(String, [[u32; 9]; 9])>,
+    reproducer_input: String,
+    // when the end of game reveal began, in egui time
+    reveal_started: Option<f64>,
+    // developer mode: the computer's next move, played instead of searching when its turn comes
+    forced_move: Option<game::Move>,
+    forced_move_input: String,
+    // the seed "Partita a caso" uses instead of a fresh one, when it parses
+    seed_input: String,
+    // a forced move standing in for the worker's answer, picked up like one
+    injected_move: Option<game::Move>,
+    // the index in `game_moves` "Analizza partita" is working on
+    reviewing: Option<usize>,
+    // the index in `states` shown instead of the latest one while scrubbing through the game
+    viewing: Option<usize>,
+    // "Modalità libera": every click puts down this mark, and the engine is never asked
+    sandbox: Option<game::Mark>,
+    // "Due giocatori": two people take turns at the board and the engine never answers
+    hotseat: bool,
+    // the mark that offered a draw to the other player, in hotseat
+    draw_offer: Option<game::Mark>,
+    // when "Incolla partita" asked for the clipboard, in egui time; it comes back as a paste
+    // event, or not at all if there's no text in it
+    awaiting_paste: Option<f64>,
+    // whether every legal reply on the board walks into a `Searcher::trap`, for that board
+    trapped: Option<(game::OuterBoard, bool)>,
 }
 
-impl Default for App {
-    fn default() -> Self {
-        let (req_tx, req_rx) = sync_channel::<(game::Mark, game::OuterBoard)>(1);
-        let (resp_tx, resp_rx) = sync_channel::<Option<(game::Move, i32)>>(1);
+// a panicking search takes the thread down with it, which shows up as a disconnected channel
+fn spawn_worker() -> (SyncSender<SearchRequest>, Receiver<SearchResponse>) {
+    let (req_tx, req_rx) = sync_channel::<SearchRequest>(1);
+    let (resp_tx, resp_rx) = sync_channel::<SearchResponse>(1);
 
-        std::thread::spawn(move || {
-            for (player, state) in req_rx {
-                let result = resp_tx.send(state.best_move(player));
-                if result.is_err() {
-                    break;
+    std::thread::spawn(move || {
+        for req in req_rx {
+            let response = match req.kind {
+                SearchKind::Play => {
+                    let config = game::searcher::SearchConfig {
+                        cancel: Some(req.cancel.clone()),
+                        ..req.config
+                    };
+                    let (best, stats) = game::searcher::Searcher::search(
+                        &req.board,
+                        req.player,
+                        config,
+                        req.options,
+                    );
+                    SearchResponse::Move(best, stats)
                 }
+                SearchKind::Hint => {
+                    let options = game::searcher::SearchOptions {
+                        max_moves_considered: None,
+                        ..req.options
+                    };
+                    let (best, _) = req.board.best_move(req.player, options);
+                    SearchResponse::Hint(best.map(|(r#move, _)| r#move))
+                }
+                SearchKind::Analyze
+                | SearchKind::Coach
+                | SearchKind::Defense
+                | SearchKind::Review => {
+                    // the analysis is for the full-strength engine, even when play is weakened
+                    let options = game::searcher::SearchOptions {
+                        max_moves_considered: None,
+                        ..req.options
+                    };
+                    let lines = match req.kind {
+                        SearchKind::Coach | SearchKind::Review => usize::MAX,
+                        _ => ANALYSIS_LINES,
+                    };
+                    let config = game::searcher::SearchConfig {
+                        cancel: Some(req.cancel.clone()),
+                        ..Default::default()
+                    };
+                    let (top, stats) = req.board.analyze(req.player, config, options, lines);
+                    let pv = top.first().map_or_else(Vec::new, |&(best, _)| {
+                        game::searcher::Searcher::principal_variation(
+                            &req.board, req.player, options, best,
+                        )
+                    });
+                    let analysis = Analysis {
+                        board: req.board,
+                        player: req.player,
+                        top,
+                        pv,
+                        stats,
+                    };
+                    match req.kind {
+                        SearchKind::Coach => SearchResponse::Coaching(analysis),
+                        SearchKind::Defense => SearchResponse::Defense(analysis),
+                        SearchKind::Review => SearchResponse::Review(analysis),
+                        _ => SearchResponse::Analysis(analysis),
+                    }
+                }
+            };
+            // cancelled while searching: nobody's waiting for this answer any more
+            if req.cancel.swap(true, Ordering::Relaxed) {
+                continue;
             }
-        });
+            let result = resp_tx.send(response);
+            if result.is_err() {
+                break;
+            }
+        }
+    });
 
-        Self {
-            random_fill_percentage: 0.5,
+    (req_tx, resp_rx)
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new(settings::AppSettings::default())
+    }
+}
+
+impl App {
+    fn new(settings: settings::AppSettings) -> Self {
+        Self::with_hotseat(settings, false)
+    }
+
+    // whether the computer opens depends on hotseat, so it has to be known before the first
+    // move is asked for
+    fn with_hotseat(settings: settings::AppSettings, hotseat: bool) -> Self {
+        let (req_tx, resp_rx) = spawn_worker();
+        let root = GameState::new_game(game::OuterBoard::default(), &settings);
+
+        let mut this = Self {
+            tutorial_step: (!settings.tutorial_seen).then_some(0),
+            settings,
+            settings_open: false,
             req_tx,
             resp_rx,
             thinking: false,
-            states: vec![],
+            thinking_quietly: false,
+            cancel: Arc::new(AtomicBool::new(false)),
+            analysis: None,
+            coaching_analysis: None,
+            defense: None,
+            coaching_tip: None,
+            hint: None,
+            clock: None,
+            remaining_x: std::time::Duration::ZERO,
+            remaining_o: std::time::Duration::ZERO,
+            playing_for_me: false,
+            autoplaying: false,
+            autoplay_next: None,
+            message: None,
+            load_error: None,
+            worker_crashed: false,
+            zoomed: None,
+            states: vec![root],
+            redo_stack: vec![],
+            redo_moves: vec![],
+            start: Some(reproducer::Start::Empty),
+            game_moves: vec![],
+            recorded_moves: 0,
+            position_db: None,
+            game_log: game_log::GameLog::new(),
+            fullscreen_applied: None,
+            editor: None,
+            logged_cell_counts: None,
+            trapped: None,
+            reproducer_input: String::new(),
+            reveal_started: None,
+            forced_move: None,
+            forced_move_input: String::new(),
+            seed_input: String::new(),
+            injected_move: None,
+            reviewing: None,
+            viewing: None,
+            sandbox: None,
+            hotseat,
+            draw_offer: None,
+            awaiting_paste: None,
+        };
+        this.reset_clocks();
+        this.start_computer_if_its_turn();
+        this
+    }
+
+    fn reset_clocks(&mut self) {
+        self.clock = self.settings.clock_enabled.then_some(self.settings.clock);
+        let initial = self
+            .clock
+            .map_or(std::time::Duration::ZERO, |clock| clock.initial);
+        self.remaining_x = initial;
+        self.remaining_o = initial;
+    }
+
+    // back to what the clocks had left in the latest state
+    fn restore_clocks(&mut self) {
+        let initial = self
+            .clock
+            .map_or(std::time::Duration::ZERO, |clock| clock.initial);
+        (self.remaining_x, self.remaining_o) = self
+            .states
+            .last()
+            .and_then(|state| state.clocks)
+            .unwrap_or((initial, initial));
+    }
+
+    fn remaining(&self, mark: game::Mark) -> std::time::Duration {
+        match mark {
+            game::Mark::X => self.remaining_x,
+            game::Mark::O => self.remaining_o,
+        }
+    }
+
+    fn remaining_mut(&mut self, mark: game::Mark) -> &mut std::time::Duration {
+        match mark {
+            game::Mark::X => &mut self.remaining_x,
+            game::Mark::O => &mut self.remaining_o,
+        }
+    }
+
+    // the side to move's clock runs, whatever is being searched in the background: on the
+    // computer's turn that's its move, on the human's it's just advice. The side that runs out
+    // loses
+    fn tick_clock(&mut self, ctx: &egui::Context) {
+        let latest = *self.states.last().unwrap();
+        if self.clock.is_none()
+            || self.sandbox.is_some()
+            || latest.end.is_some()
+            || latest.board.is_over()
+        {
+            return;
+        }
+        let to_move = latest.to_move;
+        // the clock only asks for a repaint every `CLOCK_REPAINT_INTERVAL`, which `stable_dt`
+        // would take for a stall and replace with a guess
+        let elapsed = std::time::Duration::from_secs_f32(ctx.input(|i| i.unstable_dt).max(0.0));
+        let remaining = self.remaining_mut(to_move);
+        *remaining = remaining.saturating_sub(elapsed);
+        if remaining.is_zero() {
+            info!(mark = %to_move, ply = self.game_moves.len(), "clock_flagged");
+            self.cancel_search();
+            self.viewing = None;
+            self.end_game(GameEnd::Timeout(to_move));
+            return;
+        }
+        ctx.request_repaint_after(CLOCK_REPAINT_INTERVAL);
+    }
+
+    fn start_computer_if_its_turn(&mut self) {
+        if self.hotseat || self.thinking || self.game_over() || self.to_move() != self.computer() {
+            return;
+        }
+        self.request_search(self.computer());
+    }
+
+    fn request_search(&mut self, player: game::Mark) {
+        if self.sandbox.is_some() {
+            return;
+        }
+        if player == self.computer()
+            && let Some(forced) = self.forced_move.take()
+        {
+            if self.board().make_move(forced).is_some() {
+                info!("move" = %forced, "forced_move_injected");
+                self.injected_move = Some(forced);
+                self.thinking = true;
+                self.thinking_quietly = false;
+                return;
+            }
+            self.message = Some(format!(
+                "La mossa forzata {forced} non è valida qui, la CPU sceglie da sola"
+            ));
+        }
+        // straight to the board, so `thinking` never goes up for a move there's no choice in
+        if self.settings.instant_moves
+            && let [only] = self.board().possible_moves(player)[..]
+            && let Some(child) = self.board().make_move(only)
+        {
+            info!("move" = %only, "only_move_played");
+            let eval = game::searcher::Searcher::heuristic(
+                &child,
+                player,
+                !player,
+                self.variant(),
+                &self.weights(),
+                self.draw_score(),
+            );
+            let stats = game::searcher::SearchStats {
+                nodes: 0,
+                depth: 0,
+                elapsed: std::time::Duration::ZERO,
+                shortcut: Some(game::searcher::Shortcut::OnlyMove),
+                timed_out: false,
+            };
+            self.apply_engine_move(Some((only, eval)), stats);
+            return;
+        }
+        self.send_request(SearchKind::Play, player);
+    }
+
+    fn request_analysis(&mut self) {
+        self.send_request(SearchKind::Analyze, self.to_move());
+    }
+
+    // the coach looks at every position the human is about to move in, once
+    fn request_coaching_if_needed(&mut self) {
+        if self.settings.coaching
+            && self.human_can_move()
+            && self
+                .coaching_analysis
+                .as_ref()
+                .is_none_or(|analysis| analysis.board != self.board())
+        {
+            self.send_request(SearchKind::Coach, self.mover());
+        }
+    }
+
+    // sends the move at `index` off for review, or finishes once there are none left
+    fn review_move(&mut self, index: usize) {
+        match self.game_moves.get(index) {
+            Some(&played) => {
+                self.reviewing = Some(index);
+                self.send_request_for(SearchKind::Review, played.r#move.player, played.board);
+            }
+            None => {
+                info!(moves = self.game_moves.len(), "review_done");
+                self.reviewing = None;
+            }
+        }
+    }
+
+    // a save only keeps the states, so the moves between them are worked back out
+    fn rebuild_game_moves(&mut self) {
+        // older saves only recorded resignations and agreed draws
+        for state in &mut self.states {
+            state.settle_end();
+        }
+        self.game_moves.clear();
+        for i in 1..self.states.len() {
+            let before = self.states[i - 1].board;
+            let after = self.states[i].board;
+            let mut moves = vec![];
+            if let Some(first) = self.states[i].last_player_move
+                && let Some(middle) = before.make_move(first)
+                && (middle == after || middle.diff(&after).is_some())
+            {
+                moves.push((before, first));
+                moves.extend(middle.diff(&after).map(|second| (middle, second)));
+            } else {
+                moves.extend(before.diff(&after).map(|r#move| (before, r#move)));
+            }
+            for (board, r#move) in moves {
+                let eval = game::searcher::Searcher::heuristic(
+                    &board.make_move(r#move).unwrap(),
+                    self.states[i].computer(),
+                    !r#move.player,
+                    self.states[i].variant,
+                    &self.weights(),
+                    self.draw_score(),
+                );
+                self.game_moves.push(PlayedMove {
+                    state: i,
+                    board,
+                    r#move,
+                    eval,
+                    annotation: None,
+                });
+            }
+        }
+        self.recorded_moves = self.game_moves.len();
+    }
+
+    fn coach_move(&mut self, board: game::OuterBoard, played: game::Move) {
+        let Some(analysis) = self
+            .coaching_analysis
+            .as_ref()
+            .filter(|analysis| analysis.board == board)
+        else {
+            return;
+        };
+        let eval_of = |r#move| {
+            analysis
+                .top
+                .iter()
+                .find(|&&(m, _)| m == r#move)
+                .map(|&(_, eval)| eval)
+        };
+        if let Some((best, best_eval)) = analysis.best()
+            && let Some(played_eval) = eval_of(played)
+        {
+            let loss = (best_eval as i64 - played_eval as i64).min(i32::MAX as i64) as i32;
+            if loss > self.settings.blunder_threshold {
+                info!("move" = ?played, "best" = ?best, loss, "coaching_tip");
+                self.coaching_tip = Some(CoachingTip {
+                    played,
+                    best,
+                    loss,
+                    revealed: false,
+                });
+            }
+        }
+    }
+
+    fn send_request(&mut self, kind: SearchKind, player: game::Mark) {
+        self.send_request_for(kind, player, self.board());
+    }
+
+    fn send_request_for(&mut self, kind: SearchKind, player: game::Mark, board: game::OuterBoard) {
+        if self.sandbox.is_some() {
+            return;
+        }
+        // only the computer plays at the chosen difficulty, "Gioca per me" keeps the default
+        let by_computer = player == self.computer();
+        let difficulty = if self.autoplaying {
+            self.settings.autoplay_difficulty(player)
+        } else if by_computer {
+            self.settings.difficulty
+        } else {
+            game::searcher::Difficulty::default()
+        };
+        let mut config = difficulty.config();
+        // on the clock the computer never thinks longer than its share of what it has left
+        if by_computer
+            && kind == SearchKind::Play
+            && let Some(clock) = self.clock
+        {
+            let budget = self.remaining(player) / CLOCK_MOVES_LEFT + clock.increment / 2;
+            config.max_time = config.max_time.min(budget);
+        }
+        self.cancel = Arc::new(AtomicBool::new(false));
+        let request = SearchRequest {
+            kind,
+            player,
+            board,
+            config,
+            cancel: self.cancel.clone(),
+            options: game::searcher::SearchOptions {
+                variant: self.variant(),
+                max_moves_considered: match kind {
+                    SearchKind::Play if by_computer && self.settings.coaching => self
+                        .settings
+                        .max_moves_considered
+                        .or(Some(COACHING_MOVES_CONSIDERED)),
+                    _ => self.settings.max_moves_considered,
+                },
+                weights: self.weights(),
+                contempt: self.settings.contempt,
+                ..Default::default()
+            },
+        };
+        // the channel only closes if the worker died, so a fresh one gets a second chance
+        if let Err(std::sync::mpsc::SendError(request)) = self.req_tx.send(request) {
+            self.restart_worker();
+            if self.req_tx.send(request).is_err() {
+                return;
+            }
+        }
+        self.thinking = true;
+        self.thinking_quietly = matches!(kind, SearchKind::Coach | SearchKind::Review);
+    }
+
+    // stops the search in flight; if the worker got to answer first, the answer is on its way
+    // and gets thrown away here
+    fn cancel_search(&mut self) {
+        if !self.thinking {
+            return;
+        }
+        if self.injected_move.take().is_none() && self.cancel.swap(true, Ordering::Relaxed) {
+            let _ = self.resp_rx.recv();
+        }
+        info!("search_cancelled");
+        self.thinking = false;
+        self.playing_for_me = false;
+        self.autoplaying = false;
+        self.reviewing = None;
+    }
+
+    // asks for the side to move's move once `AUTOPLAY_INTERVAL` has passed since the last one
+    // landed, until the game ends
+    fn drive_autoplay(&mut self, ctx: &egui::Context) {
+        if !self.autoplaying || self.thinking {
+            return;
+        }
+        if self.game_over() {
+            self.autoplaying = false;
+            self.autoplay_next = None;
+            info!(
+                ply = self.game_moves.len(),
+                id = self.game_id(),
+                "autoplay_finished"
+            );
+            return;
+        }
+        let now = ctx.input(|i| i.time);
+        match self.autoplay_next {
+            Some(next) if now >= next => {
+                self.autoplay_next = None;
+                self.request_search(self.to_move());
+            }
+            Some(next) => ctx.request_repaint_after(std::time::Duration::from_secs_f64(next - now)),
+            None => {
+                self.autoplay_next = Some(now + AUTOPLAY_INTERVAL.as_secs_f64());
+                ctx.request_repaint_after(AUTOPLAY_INTERVAL);
+            }
+        }
+    }
+
+    fn restart_worker(&mut self) {
+        error!("worker_died");
+        (self.req_tx, self.resp_rx) = spawn_worker();
+        self.thinking = false;
+        self.playing_for_me = false;
+        self.autoplaying = false;
+        self.worker_crashed = true;
+        self.message = Some("Il motore si è bloccato ed è stato riavviato".to_string());
+    }
+
+    fn root_state(&self, board: game::OuterBoard) -> GameState {
+        GameState::new_game(board, &self.settings)
+    }
+
+    // the state before the human's costliest move, when it cost more than the blunder threshold;
+    // "Analizza partita" knows what each move lost, otherwise the eval swing stands in for it
+    fn last_decision_point(&self) -> Option<usize> {
+        let mut previous_eval = 0;
+        let mut worst: Option<(i64, usize)> = None;
+        for played in &self.game_moves {
+            let loss = match played.annotation {
+                Some(annotation) => annotation.best_eval as i64 - annotation.played_eval as i64,
+                None => eval_drop(
+                    played.r#move.player == self.computer(),
+                    previous_eval,
+                    played.eval,
+                ),
+            };
+            previous_eval = played.eval;
+            // ties go to the later move, the one closest to how the game was lost
+            if played.r#move.player == self.human()
+                && loss > self.settings.blunder_threshold as i64
+                && worst.is_none_or(|(worst_loss, _)| loss >= worst_loss)
+            {
+                worst = Some((loss, played.state.saturating_sub(1)));
+            }
+        }
+        worst.map(|(_, state)| state)
+    }
+
+    // a slider and an eval chart over `states`; picking anything but the last one shows
+    // that position everywhere until "Torna alla partita"
+    fn show_history(&mut self, ui: &mut egui::Ui) {
+        let latest = self.states.len() - 1;
+        let mut index = self.displayed_index();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(index > 0, egui::Button::new("◀"))
+                .on_hover_text("Posizione precedente")
+                .clicked()
+            {
+                index -= 1;
+            }
+            if ui
+                .add_enabled(index < latest, egui::Button::new("▶"))
+                .on_hover_text("Posizione successiva")
+                .clicked()
+            {
+                index += 1;
+            }
+            ui.add(egui::Slider::new(&mut index, 0..=latest).text("Posizione"));
+        });
+        if self.viewing.is_some() && self.human_can_move_here() {
+            ui.label("Gioca una mossa per ripartire da qui: le mosse successive si perdono");
+        }
+        let evals: Vec<_> = self.states.iter().map(|state| state.eval).collect();
+        if let Some(clicked) = draw_eval_chart(ui, &evals, index) {
+            index = clicked;
+        }
+        let decision_point = self.last_decision_point();
+        if ui
+            .add_enabled(
+                decision_point.is_some(),
+                egui::Button::new("Torna all'errore più grave"),
+            )
+            .on_hover_text("La posizione prima della tua mossa che ti è costata di più")
+            .on_disabled_hover_text("Nessun errore grave in questa partita")
+            .clicked()
+            && let Some(point) = decision_point
+        {
+            index = point;
+        }
+        if self.viewing.is_some() && ui.button("Torna alla partita").clicked() {
+            index = latest;
         }
+        self.viewing = (index != latest).then_some(index);
     }
 }
 
@@ -107,6 +1189,15 @@ fn main() -> Result<()> {
         .with(tracing_error::ErrorLayer::default())
         .try_init()?;
 
+    match std::env::args().nth(1).as_deref() {
+        Some("strength-test") => return strength_test::run(),
+        Some("bench") => return bench::run(),
+        Some("profile") => return profile::run(),
+        Some("export") => return export::run(),
+        Some("reproduce") => return reproducer::run(std::env::args().nth(2)),
+        _ => {}
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default().with_icon(eframe::icon_data::from_png_bytes(
             include_bytes!("../assets/icon.png"),
@@ -116,135 +1207,1412 @@ fn main() -> Result<()> {
     eframe::run_native(
         "Supertris",
         options,
-        Box::new(|_cc| Ok(Box::new(App::default()))),
+        Box::new(|cc| {
+            let app = App::new(settings::AppSettings::load(cc.storage));
+            app.log_new_game("empty");
+            Ok(Box::new(app))
+        }),
     )
     .map_err(|e| eyre!("{e:?}"))?;
     Ok(())
 }
 
 impl App {
+    // everything reading the game goes through here, so scrubbing moves all of it together
+    fn state(&self) -> &GameState {
+        &self.states[self.displayed_index()]
+    }
+
+    fn displayed_index(&self) -> usize {
+        let latest = self.states.len() - 1;
+        self.viewing.map_or(latest, |index| index.min(latest))
+    }
+
     fn board(&self) -> game::OuterBoard {
-        self.states
-            .last()
-            .map_or_else(|| game::OuterBoard::default(), |s| s.board)
+        self.state().board
     }
 
     fn eval(&self) -> i32 {
-        self.states.last().map_or(0, |s| s.eval)
+        self.state().eval
     }
 
     fn last_player_move(&self) -> Option<game::Move> {
-        self.states.last().and_then(|s| s.last_player_move)
+        self.state().last_player_move
     }
 
     fn last_computer_move(&self) -> Option<game::Move> {
-        self.states.last().and_then(|s| s.last_computer_move)
+        self.state().last_computer_move
     }
 
-    fn overall_winner(&self) -> Option<game::Mark> {
-        self.states.last().map_or(None, |s| s.board.overall_winner)
+    fn is_last_move(&self, outer: (u8, u8), inner: (u8, u8)) -> bool {
+        self.overall_winner().is_none()
+            && [self.last_computer_move(), self.last_player_move()]
+                .into_iter()
+                .flatten()
+                .any(|m| m.outer == outer && m.inner == inner)
     }
-}
 
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        egui::SidePanel::left("side_panel").show(ctx, |ui| {
-            ui.heading("Supertris");
-            ui.horizontal(|ui| {
-                ui.label("Basta col solito tris, prova Supertris!");
-            });
-            ui.separator();
+    fn end(&self) -> Option<GameEnd> {
+        self.state().end
+    }
 
-            ui.horizontal(|ui| {
-                if ui.button("Reset").clicked() {
-                    *self = App::default();
-                }
+    fn overall_winner(&self) -> Option<game::Mark> {
+        match self.end() {
+            Some(GameEnd::Resignation(mark) | GameEnd::Timeout(mark)) => Some(!mark),
+            Some(GameEnd::Line(line_owner)) => Some(self.variant().winner(line_owner)),
+            Some(GameEnd::Draw(_)) | None => None,
+        }
+    }
 
-                let random_btn = ui.button("Partita a caso");
-                if random_btn.clicked() {
-                    self.states.clear();
-                    self.states.push(GameState::root(game::OuterBoard::random(
-                        self.random_fill_percentage,
-                    )));
-                }
+    fn game_over(&self) -> bool {
+        self.end().is_some() || self.board().is_over()
+    }
 
-                if ui.button("Gioca per me").clicked() {
-                    if !self.thinking {
-                        self.req_tx.send((game::HUMAN_MARK, self.board())).unwrap();
-                        self.thinking = true;
-                    }
-                }
+    fn weights(&self) -> game::searcher::HeuristicWeights {
+        self.settings.personality.weights()
+    }
 
-                if ui.button("Annulla mossa").clicked() {
-                    assert!(!self.thinking);
-                    self.states.pop();
-                }
-            });
-            ui.add(
-                egui::Slider::new(&mut self.random_fill_percentage, 0.0..=1.0)
-                    .text("Percentuale di caselle riempite"),
-            );
-            if ui.button("Inizia la CPU").clicked() {
-                assert!(!self.thinking);
-                if self.states.is_empty() {
-                    self.req_tx.send((game::COMPUTER_MARK, self.board())).unwrap();
-                    self.thinking = true;
+    // what a drawn game is worth to the side an eval is for; every search gets the same contempt
+    fn draw_score(&self) -> i32 {
+        game::searcher::SearchOptions {
+            contempt: self.settings.contempt,
+            ..Default::default()
+        }
+        .draw_score()
+    }
+
+    fn variant(&self) -> game::Variant {
+        self.state().variant
+    }
+
+    fn to_move(&self) -> game::Mark {
+        self.state().to_move
+    }
+
+    fn human(&self) -> game::Mark {
+        self.state().human
+    }
+
+    // the eval as the perspective in the settings wants it read
+    fn shown_eval(&self) -> i32 {
+        self.settings.eval_perspective.apply(
+            self.eval(),
+            self.computer(),
+            self.to_move(),
+            self.human(),
+        )
+    }
+
+    fn x_win_probability(&self) -> f64 {
+        let computer = win_probability(self.eval());
+        match self.computer() {
+            game::Mark::X => computer,
+            game::Mark::O => 1.0 - computer,
+        }
+    }
+
+    fn computer(&self) -> game::Mark {
+        self.state().computer()
+    }
+
+    fn human_can_move(&self) -> bool {
+        self.viewing.is_none() && self.human_can_move_here()
+    }
+
+    // the human's turn in the position on screen; a move there while scrubbing through the
+    // game branches it from that position
+    fn human_can_move_here(&self) -> bool {
+        !self.thinking
+            && !self.autoplaying
+            && self.sandbox.is_none()
+            && !self.game_over()
+            && self.to_move() == self.mover()
+    }
+
+    // the mark of whoever is at the board: the human's, or in hotseat the side to move
+    fn mover(&self) -> game::Mark {
+        if self.hotseat {
+            self.to_move()
+        } else {
+            self.human()
+        }
+    }
+
+    // forgets every state after the one on screen, as undoing back to it would
+    fn branch_from_displayed(&mut self) {
+        let len = self.displayed_index() + 1;
+        self.viewing = None;
+        if len == self.states.len() {
+            return;
+        }
+        self.states.truncate(len);
+        self.game_moves.retain(|played| played.state < len);
+        self.recorded_moves = self.recorded_moves.min(self.game_moves.len());
+        self.clear_redo();
+        info!(ply = self.game_moves.len(), id = self.game_id(), "branched");
+    }
+
+    // whether a click on the board puts down `placing_mark`
+    fn can_place(&self) -> bool {
+        match self.sandbox {
+            Some(_) => !self.thinking && self.viewing.is_none() && !self.game_over(),
+            None => self.human_can_move_here(),
+        }
+    }
+
+    fn placing_mark(&self) -> game::Mark {
+        self.sandbox.unwrap_or(self.mover())
+    }
+
+    // a sandbox move only goes into the history, there's no one to answer it and it's no
+    // game worth logging or reproducing
+    fn place_sandbox_mark(&mut self, r#move: game::Move, new_board: game::OuterBoard) {
+        let mut new_state = *self.state();
+        new_state.board = new_board;
+        if r#move.player == new_state.human {
+            new_state.last_player_move = Some(r#move);
+            new_state.last_computer_move = None;
+        } else {
+            new_state.last_computer_move = Some(r#move);
+            new_state.last_player_move = None;
+        }
+        new_state.to_move = !r#move.player;
+        new_state.eval = game::searcher::Searcher::heuristic(
+            &new_board,
+            new_state.computer(),
+            new_state.to_move,
+            new_state.variant,
+            &self.weights(),
+            self.draw_score(),
+        );
+        new_state.settle_end();
+        self.states.push(new_state);
+        self.clear_redo();
+        self.start = None;
+        self.message = None;
+    }
+
+    // what the worker answered a `SearchKind::Play` with, for either side
+    fn apply_engine_move(
+        &mut self,
+        computer_move: Option<(game::Move, i32)>,
+        stats: game::searcher::SearchStats,
+    ) {
+        // the move goes on top of the latest state, so that's the one to show
+        self.viewing = None;
+        if let Some((r#move, eval)) = computer_move {
+            let _span = tracing::debug_span!("computer_move", "move" = ?r#move, eval).entered();
+            if let Some(new_board) = self.board().make_move(r#move) {
+                let old_board = self.board();
+                if let Some(message) = stats.shortcut.and_then(|shortcut| {
+                    describe_shortcut(shortcut, r#move.player == self.computer())
+                }) {
+                    self.message = Some(message.to_string());
+                }
+
+                let is_fake_human = r#move.player != self.computer();
+
+                if is_fake_human {
+                    // searched from the human's side, so flip it to the computer's
+                    push_move(&mut self.states, r#move, new_board, eval.saturating_neg());
+                    self.log_move(old_board, r#move, Some(stats));
+                    if self.board().is_over() {
+                        self.playing_for_me = false;
+                        self.thinking = false;
+                    } else if self.autoplaying {
+                        // the timer asks for the answer
+                        self.thinking = false;
+                    } else {
+                        self.request_search(self.computer());
+                    }
                 } else {
-                    error!("cannot_start_cpu_after_player_move");
+                    push_move(&mut self.states, r#move, new_board, eval);
+                    self.log_move(old_board, r#move, Some(stats));
+
+                    if self.board().is_over() {
+                        self.playing_for_me = false;
+                    }
+
+                    if self.playing_for_me && self.settings.keep_playing_for_me {
+                        self.request_search(self.human());
+                    } else {
+                        self.playing_for_me = false;
+                        self.thinking = false;
+                    }
+                }
+            } else {
+                error!("computer_move_invalid");
+                self.playing_for_me = false;
+                self.thinking = false;
+            }
+        } else {
+            if !self.board().is_over() {
+                error!("no_computer_move");
+            }
+            self.playing_for_me = false;
+            self.thinking = false;
+        }
+    }
+
+    fn log_move(
+        &mut self,
+        board: game::OuterBoard,
+        r#move: game::Move,
+        stats: Option<game::searcher::SearchStats>,
+    ) {
+        self.clear_redo();
+        self.game_moves.push(PlayedMove {
+            state: self.states.len() - 1,
+            board,
+            r#move,
+            eval: self.eval(),
+            annotation: None,
+        });
+        info!(
+            ply = self.game_moves.len(),
+            mover = %r#move.player,
+            "move" = %r#move,
+            eval = self.eval(),
+            nodes = stats.map(|stats| stats.nodes),
+            depth = stats.map(|stats| stats.depth),
+            shortcut = ?stats.and_then(|stats| stats.shortcut),
+            "move_made"
+        );
+        if let Some(clock) = self.clock {
+            *self.remaining_mut(r#move.player) += clock.increment;
+            self.states.last_mut().unwrap().clocks = Some((self.remaining_x, self.remaining_o));
+        }
+        if self.settings.game_log_enabled {
+            self.game_log
+                .record(&self.settings.game_log_path, r#move, self.eval(), stats);
+            self.logged_cell_counts = None;
+        }
+        if self.game_over() {
+            self.log_game_over();
+        }
+    }
+
+    // every transition of the game shows up as one of `new_game`, `move_made`, `undo`, `redo`,
+    // `game_over`, `game_saved` or `game_loaded`, sharing the names of their fields:
+    // `ply` is how many moves have been played, `id` is `game_id`
+    fn log_new_game(&self, source: &'static str) {
+        info!(
+            source,
+            ply = self.game_moves.len(),
+            variant = ?self.variant(),
+            ruleset = ?self.board().ruleset,
+            to_move = %self.to_move(),
+            human = %self.human(),
+            id = self.game_id(),
+            "new_game"
+        );
+    }
+
+    fn log_game_over(&self) {
+        let result = match self.overall_winner() {
+            Some(game::Mark::X) => "x_wins",
+            Some(game::Mark::O) => "o_wins",
+            None => "draw",
+        };
+        info!(
+            ply = self.game_moves.len(),
+            result,
+            end = ?self.end(),
+            id = self.game_id(),
+            "game_over"
+        );
+    }
+
+    // like a fresh `App`, but keeps what outlives a single game
+    fn restart(&mut self) {
+        let position_db = self.position_db.take();
+        let game_log = std::mem::replace(&mut self.game_log, game_log::GameLog::new());
+        *self = App::with_hotseat(self.settings.clone(), self.hotseat);
+        self.position_db = position_db;
+        self.game_log = game_log;
+        self.game_log.next_game();
+    }
+
+    fn record_finished_game(&mut self) {
+        if !self.settings.record_positions
+            || !self.game_over()
+            || self.recorded_moves >= self.game_moves.len()
+        {
+            return;
+        }
+        let moves: Vec<_> = self.game_moves[self.recorded_moves..]
+            .iter()
+            .map(|played| (played.board, played.r#move))
+            .collect();
+        self.recorded_moves = self.game_moves.len();
+        let winner = self.overall_winner();
+        let db = self
+            .position_db
+            .get_or_insert_with(position_db::PositionDb::load);
+        db.record_game(&moves, winner);
+        db.save();
+    }
+
+    fn debug_report(&self) -> String {
+        use std::fmt::Write;
+
+        let board = self.board();
+        let mut report = String::new();
+        writeln!(report, "Tabellone:\n{board}").unwrap();
+        writeln!(report, "Sottotabella attiva: {:?}", board.active_square).unwrap();
+        writeln!(
+            report,
+            "Variante: {:?}, regole: {:?}",
+            self.variant(),
+            board.ruleset
+        )
+        .unwrap();
+        writeln!(report, "Tocca a: {}", self.to_move()).unwrap();
+        writeln!(report, "Valutazione mostrata: {}", self.eval()).unwrap();
+
+        match self
+            .analysis
+            .as_ref()
+            .filter(|analysis| analysis.board == board)
+        {
+            Some(analysis) => {
+                match analysis.best() {
+                    Some((r#move, eval)) => writeln!(
+                        report,
+                        "Mossa migliore: {} ({:?}), valutazione {eval}",
+                        describe_move(r#move),
+                        r#move
+                    ),
+                    None => writeln!(report, "Mossa migliore: nessuna"),
                 }
+                .unwrap();
+                writeln!(
+                    report,
+                    "Variante principale: {}",
+                    describe_line(&analysis.pv)
+                )
+                .unwrap();
+                writeln!(
+                    report,
+                    "Nodi: {}, profondità: {}, tempo: {} ms",
+                    analysis.stats.nodes,
+                    analysis.stats.depth,
+                    analysis.stats.elapsed.as_millis()
+                )
+                .unwrap();
             }
+            None => writeln!(report, "Analisi: nessuna (usa \"Valuta posizione\")").unwrap(),
+        }
+
+        let breakdown = game::searcher::Searcher::heuristic_breakdown(
+            &board,
+            self.computer(),
+            self.to_move(),
+            self.variant(),
+            &self.weights(),
+            self.draw_score(),
+        );
+        writeln!(report, "Euristica per {}:", self.computer()).unwrap();
+        for (label, value) in breakdown_rows(&breakdown) {
+            writeln!(report, "  {label}: {value}").unwrap();
+        }
+        writeln!(report, "  Totale: {}", breakdown.total()).unwrap();
+        report
+    }
 
+    // with balanced starts on, draws boards until one looks even to the heuristic
+    fn random_start(&mut self) -> reproducer::Start {
+        let fill = self.settings.random_fill_percentage;
+        let attempts = if self.settings.balanced_random {
+            BALANCED_RANDOM_ATTEMPTS
+        } else {
+            1
+        };
+        // a typed seed is a position someone wants exactly, balanced or not
+        if let Ok(seed) = self.seed_input.trim().parse() {
+            info!(seed, fill, "random_start_seeded");
+            return reproducer::Start::Random { seed, fill };
+        }
+        let mut start = reproducer::Start::Empty;
+        for attempt in 1..=attempts {
+            let seed = rand::random();
+            start = reproducer::Start::Random { seed, fill };
+            let root = self.root_state(start.board());
+            let eval = game::searcher::Searcher::heuristic(
+                &root.board,
+                root.computer(),
+                root.to_move,
+                root.variant,
+                &self.weights(),
+                self.draw_score(),
+            );
+            if !self.settings.balanced_random || eval.abs() <= self.settings.balance_band {
+                info!(attempt, eval, seed, "random_start_chosen");
+                return start;
+            }
+        }
+        info!(attempts, "random_start_unbalanced");
+        self.message = Some(format!(
+            "Nessuna posizione equilibrata in {attempts} tentativi, si gioca l'ultima"
+        ));
+        start
+    }
+
+    // eight hex digits from every board the game went through, so the same game always gets
+    // the same ID, also once saved and loaded back
+    fn game_id(&self) -> String {
+        let boards: Vec<_> = self.states.iter().map(|state| state.board).collect();
+        let bytes = bincode::encode_to_vec(boards, bincode::config::standard())
+            .expect("encoding a board can't fail");
+        format!("{:08x}", game::fnv1a(&bytes) as u32)
+    }
+
+    fn reproducer(&self) -> Option<reproducer::Reproducer> {
+        let root = &self.states[0];
+        Some(reproducer::Reproducer {
+            start: self.start?,
+            variant: root.variant,
+            ruleset: root.board.ruleset,
+            first: self
+                .game_moves
+                .first()
+                .map_or(self.to_move(), |played| played.r#move.player),
+            moves: self.game_moves.iter().map(|played| played.r#move).collect(),
+        })
+    }
+
+    // rebuilds the game move by move, the way it was played, without logging it again
+    fn load_reproducer(&mut self, reproducer: &reproducer::Reproducer) {
+        let boards = match reproducer.replay() {
+            Ok(boards) => boards,
+            Err(e) => {
+                self.message = Some(format!("Riproduttore non valido: {e}"));
+                return;
+            }
+        };
+        self.restart();
+        self.start = Some(reproducer.start);
+        self.states = vec![GameState {
+            to_move: reproducer.first,
+            ..GameState::root(boards[0], reproducer.variant, self.settings.human_mark)
+        }];
+        let human = self.settings.human_mark;
+        for (&r#move, window) in reproducer.moves.iter().zip(boards.windows(2)) {
+            let [old_board, new_board] = [window[0], window[1]];
+            let eval = game::searcher::Searcher::heuristic(
+                &new_board,
+                !human,
+                !r#move.player,
+                reproducer.variant,
+                &self.weights(),
+                self.draw_score(),
+            );
+            if r#move.player == human {
+                let mut new_state = *self.states.last().unwrap();
+                new_state.last_player_move = Some(r#move);
+                self.states.push(new_state);
+            }
+            let state = self.states.last_mut().unwrap();
+            if r#move.player != human {
+                state.last_computer_move = Some(r#move);
+            }
+            state.board = new_board;
+            state.to_move = !r#move.player;
+            state.eval = eval;
+            state.settle_end();
+            self.game_moves.push(PlayedMove {
+                state: self.states.len() - 1,
+                board: old_board,
+                r#move,
+                eval,
+                annotation: None,
+            });
+        }
+        self.log_new_game("reproducer");
+        self.start_computer_if_its_turn();
+    }
+
+    // from the empty board with the rules in the settings, as the export doesn't record them
+    fn import_moves(&mut self, path: &std::path::Path) {
+        let moves = std::fs::read_to_string(path)
+            .map_err(color_eyre::Report::from)
+            .and_then(|json| export::read_transcript(&json));
+        match moves {
+            Ok(moves) if !moves.is_empty() => {
+                info!(path = %path.display(), ply = moves.len(), "moves_imported");
+                self.load_reproducer(&reproducer::Reproducer {
+                    start: reproducer::Start::Empty,
+                    variant: self.settings.variant,
+                    ruleset: self.settings.ruleset,
+                    first: moves[0].player,
+                    moves,
+                });
+            }
+            Ok(_) => self.message = Some("Il file non contiene mosse".to_string()),
+            Err(e) => {
+                info!(error = %e, path = %path.display(), "moves_import_invalid");
+                self.message = Some(format!("Mosse non valide: {e}"));
+            }
+        }
+    }
+
+    // a bad transcript only leaves a message, `load_reproducer` checks every move first
+    fn load_pasted_transcript(&mut self, ctx: &egui::Context) {
+        let Some(asked) = self.awaiting_paste else {
+            return;
+        };
+        if ctx.input(|i| i.time) - asked > PASTE_TIMEOUT_SECONDS {
+            self.awaiting_paste = None;
+            self.message = Some("Negli appunti non c'è testo".to_string());
+            return;
+        }
+        let Some(text) = ctx.input(|i| {
+            i.events.iter().find_map(|event| match event {
+                egui::Event::Paste(text) => Some(text.clone()),
+                _ => None,
+            })
+        }) else {
+            // nothing else may come along to run the timeout
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(PASTE_TIMEOUT_SECONDS));
+            return;
+        };
+        self.awaiting_paste = None;
+        match reproducer::parse_transcript(&text, self.settings.variant, self.settings.ruleset) {
+            Ok(reproducer) => self.load_reproducer(&reproducer),
+            Err(e) => {
+                info!(error = %e, "pasted_transcript_invalid");
+                self.message = Some(format!("Partita negli appunti non valida: {e}"));
+            }
+        }
+    }
+
+    fn cell_counts(&mut self) -> [[u32; 9]; 9] {
+        match self.settings.heatmap {
+            settings::Heatmap::Off => [[0; 9]; 9],
+            settings::Heatmap::CurrentGame => {
+                let mut counts = [[0; 9]; 9];
+                for played in &self.game_moves {
+                    let r#move = played.r#move;
+                    counts[r#move.outer.0 as usize * 3 + r#move.inner.0 as usize]
+                        [r#move.outer.1 as usize * 3 + r#move.inner.1 as usize] += 1;
+                }
+                counts
+            }
+            settings::Heatmap::GameLog => {
+                let path = &self.settings.game_log_path;
+                if let Some((cached_path, counts)) = &self.logged_cell_counts
+                    && cached_path == path
+                {
+                    return *counts;
+                }
+                let counts = game_log::GameLog::cell_counts(path).unwrap_or_else(|e| {
+                    error!(error = ?e, "game_log_read_error");
+                    [[0; 9]; 9]
+                });
+                self.logged_cell_counts = Some((path.clone(), counts));
+                counts
+            }
+        }
+    }
+
+    // the computer's last move left the human no reply that doesn't hand it something
+    fn computer_set_trap(&mut self) -> bool {
+        let board = self.board();
+        if !self.settings.trap_warnings
+            || self.variant() != game::Variant::Standard
+            || !self.human_can_move()
+            || self.last_computer_move().is_none()
+        {
+            return false;
+        }
+        if let Some((cached_board, trapped)) = self.trapped
+            && cached_board == board
+        {
+            return trapped;
+        }
+        let replies = board.possible_moves(self.human());
+        let trapped = !replies.is_empty()
+            && replies.iter().all(|&reply| {
+                board.make_move(reply).is_some_and(|child| {
+                    game::searcher::Searcher::trap(&child, self.computer()).is_some()
+                })
+            });
+        if trapped {
+            info!(ply = self.states.len() - 1, "trap_detected");
+        }
+        self.trapped = Some((board, trapped));
+        trapped
+    }
+
+    fn end_game(&mut self, end: GameEnd) {
+        let mut new_state = *self.state();
+        new_state.end = Some(end);
+        self.states.push(new_state);
+        self.clear_redo();
+        self.log_game_over();
+    }
+
+    // whatever is being searched, it's for the position being undone
+    fn undo(&mut self) {
+        self.cancel_search();
+        self.redo_stack.extend(self.states.pop());
+        self.viewing = None;
+        self.coaching_tip = None;
+        self.hint = None;
+        self.draw_offer = None;
+        let len = self.states.len();
+        let first_undone = self
+            .game_moves
+            .iter()
+            .position(|played| played.state >= len)
+            .unwrap_or(self.game_moves.len());
+        self.redo_moves
+            .extend(self.game_moves.drain(first_undone..));
+        self.recorded_moves = self.recorded_moves.min(self.game_moves.len());
+        self.restore_clocks();
+        info!(ply = self.game_moves.len(), id = self.game_id(), "undo");
+    }
+
+    // puts back the last undone state; like undoing, it drops whatever was being searched
+    // for the position on screen, and if the state was undone before the computer answered,
+    // the computer gets asked again
+    fn redo(&mut self) {
+        let Some(state) = self.redo_stack.pop() else {
+            return;
+        };
+        self.cancel_search();
+        self.states.push(state);
+        self.viewing = None;
+        self.coaching_tip = None;
+        self.hint = None;
+        let index = self.states.len() - 1;
+        let first_redone = self
+            .redo_moves
+            .iter()
+            .rposition(|played| played.state != index)
+            .map_or(0, |i| i + 1);
+        self.game_moves
+            .extend(self.redo_moves.drain(first_redone..));
+        self.restore_clocks();
+        info!(ply = self.game_moves.len(), id = self.game_id(), "redo");
+        self.start_computer_if_its_turn();
+    }
+
+    fn clear_redo(&mut self) {
+        self.redo_stack.clear();
+        self.redo_moves.clear();
+    }
+}
+
+impl eframe::App for App {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, settings::STORAGE_KEY, &self.settings);
+    }
+
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let animation_time = if self.settings.power_saver {
+            0.0
+        } else {
+            egui::Style::default().animation_time
+        };
+        if ctx.style().animation_time != animation_time {
+            ctx.style_mut(|style| style.animation_time = animation_time);
+        }
+        self.load_pasted_transcript(ctx);
+        self.tick_clock(ctx);
+        self.drive_autoplay(ctx);
+        // the worker can't wake the ui up, so poll it while a search is running
+        if self.thinking {
+            ctx.request_repaint_after(WORKER_POLL_INTERVAL);
+        }
+
+        if ctx.input(|i| i.key_pressed(egui::Key::F11)) {
+            self.settings.fullscreen = !self.settings.fullscreen;
+        }
+        if self.fullscreen_applied != Some(self.settings.fullscreen) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(self.settings.fullscreen));
+            self.fullscreen_applied = Some(self.settings.fullscreen);
+        }
+
+        // in fullscreen only the board is shown, with a button to get back
+        if self.settings.fullscreen {
+            egui::Area::new("fullscreen_exit".into())
+                .anchor(egui::Align2::LEFT_TOP, egui::vec2(8.0, 8.0))
+                .order(egui::Order::Foreground)
+                .show(ctx, |ui| {
+                    if ui.button("Esci da schermo intero (F11)").clicked() {
+                        self.settings.fullscreen = false;
+                    }
+                });
+        }
+
+        let show_panel = !self.settings.fullscreen;
+        egui::SidePanel::left("side_panel").show_animated(ctx, show_panel, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Supertris");
+                let settings_btn = ui.button("⚙").on_hover_text("Impostazioni");
+                settings_btn.widget_info(|| {
+                    egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Impostazioni")
+                });
+                if settings_btn.clicked() {
+                    self.settings_open = true;
+                }
+                let fullscreen_btn = ui.button("⛶").on_hover_text("Schermo intero (F11)");
+                fullscreen_btn.widget_info(|| {
+                    egui::WidgetInfo::labeled(egui::WidgetType::Button, true, "Schermo intero")
+                });
+                if fullscreen_btn.clicked() {
+                    self.settings.fullscreen = true;
+                }
+                if ui.button("Tutorial").clicked() {
+                    self.tutorial_step = Some(0);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Basta col solito tris, prova Supertris!");
+            });
+            egui::ComboBox::from_label("Difficoltà")
+                .selected_text(self.settings.difficulty.to_string())
+                .show_ui(ui, |ui| {
+                    for difficulty in game::searcher::Difficulty::ALL {
+                        ui.selectable_value(
+                            &mut self.settings.difficulty,
+                            difficulty,
+                            difficulty.to_string(),
+                        );
+                    }
+                });
+            let mut side_changed = false;
+            ui.horizontal(|ui| {
+                ui.label("Giochi con:");
+                for (mark, text) in [
+                    (game::Mark::X, "X, muovi per primo"),
+                    (game::Mark::O, "O, apre la CPU"),
+                ] {
+                    side_changed |= ui
+                        .selectable_value(&mut self.settings.human_mark, mark, text)
+                        .changed();
+                }
+            });
+            // with nothing played yet the game starts over on the new side, otherwise the
+            // choice waits for the next one
+            if side_changed && self.game_moves.is_empty() && self.editor.is_none() {
+                self.restart();
+                self.log_new_game("empty");
+            } else if self.settings.human_mark != self.human() {
+                ui.label("(dalla prossima partita)");
+            }
             ui.separator();
 
-            ui.vertical_centered(|ui| {
-                let mut left_font_size = 1.0f32;
-                let mut right_font_size = 256.0f32;
+            if let Some(editor) = &mut self.editor {
+                match editor.show_controls(ui) {
+                    Some(editor::EditorAction::Start(board)) => {
+                        self.restart();
+                        self.start = None;
+                        self.states = vec![GameState {
+                            to_move: board.side_to_move(),
+                            ..self.root_state(board)
+                        }];
+                        self.log_new_game("editor");
+                        self.start_computer_if_its_turn();
+                    }
+                    Some(editor::EditorAction::Cancel) => self.editor = None,
+                    None => {}
+                }
+                return;
+            }
+            if ui
+                .add_enabled(!self.thinking, egui::Button::new("Modifica posizione"))
+                .clicked()
+            {
+                self.editor = Some(editor::Editor::new(self.board()));
+            }
 
-                // binary search
-                loop {
-                    if (right_font_size - left_font_size).abs() < 1.0 {
-                        break;
+            ui.horizontal(|ui| {
+                let mut sandbox = self.sandbox.is_some();
+                if ui
+                    .add_enabled(
+                        !self.thinking,
+                        egui::Checkbox::new(&mut sandbox, "Modalità libera"),
+                    )
+                    .on_hover_text("Metti i segni che vuoi, con le regole ma senza la CPU")
+                    .changed()
+                {
+                    if sandbox {
+                        info!("sandbox_entered");
+                        self.hotseat = false;
+                        self.draw_offer = None;
+                        self.sandbox = Some(self.to_move());
+                        self.coaching_tip = None;
+                    } else {
+                        info!("sandbox_left");
+                        self.sandbox = None;
+                        self.start_computer_if_its_turn();
                     }
+                }
+                if let Some(mark) = &mut self.sandbox {
+                    ui.selectable_value(mark, game::Mark::X, "X");
+                    ui.selectable_value(mark, game::Mark::O, "O");
+                }
+            });
+
+            if ui
+                .add_enabled(
+                    !self.thinking && !self.autoplaying,
+                    egui::Checkbox::new(&mut self.hotseat, "Due giocatori"),
+                )
+                .on_hover_text("Due persone si alternano sulla stessa scacchiera, senza la CPU")
+                .changed()
+            {
+                self.draw_offer = None;
+                self.coaching_tip = None;
+                self.hint = None;
+                if self.hotseat {
+                    info!("hotseat_entered");
+                    self.sandbox = None;
+                } else {
+                    info!("hotseat_left");
+                    self.start_computer_if_its_turn();
+                }
+            }
 
-                    let font_size = (left_font_size + right_font_size) / 2.0;
+            ui.horizontal(|ui| {
+                if ui.button("Reset").clicked() {
+                    self.restart();
+                    self.log_new_game("empty");
+                }
 
-                    let w = ["Valutazione:".to_string(), self.eval().to_string()]
-                        .iter()
-                        .map(|s| {
-                            ui.painter()
-                                .layout_no_wrap(
-                                    s.to_string(),
-                                    egui::FontId::proportional(font_size),
-                                    egui::Color32::WHITE,
-                                )
-                                .size()
-                                .x
-                        })
-                        .fold(0.0, f32::max);
-                    if w > ui.available_width() * 0.9 {
-                        right_font_size = font_size;
-                    } else if w < ui.available_width() * 0.9 {
-                        left_font_size = font_size;
+                // an answer still on its way would land on the new board
+                if ui
+                    .add_enabled(!self.thinking, egui::Button::new("Partita a caso"))
+                    .clicked()
+                {
+                    self.game_moves.clear();
+                    self.recorded_moves = 0;
+                    self.clear_redo();
+                    self.game_log.next_game();
+                    let start = self.random_start();
+                    self.reset_clocks();
+                    self.start = Some(start);
+                    self.viewing = None;
+                    self.states = vec![self.root_state(start.board())];
+                    self.log_new_game("random");
+                    self.start_computer_if_its_turn();
+                }
+
+                if ui
+                    .add_enabled(
+                        self.human_can_move() && !self.hotseat,
+                        egui::Button::new("Gioca per me"),
+                    )
+                    .clicked()
+                {
+                    self.playing_for_me = self.settings.keep_playing_for_me;
+                    self.request_search(self.human());
+                }
+
+                if ui
+                    .add_enabled(
+                        !self.thinking
+                            && !self.autoplaying
+                            && self.sandbox.is_none()
+                            && !self.hotseat
+                            && self.viewing.is_none()
+                            && !self.game_over(),
+                        egui::Button::new("Autopartita"),
+                    )
+                    .on_hover_text("La CPU gioca per entrambi fino alla fine")
+                    .clicked()
+                {
+                    self.autoplaying = true;
+                    info!(
+                        ply = self.game_moves.len(),
+                        id = self.game_id(),
+                        "autoplay_started"
+                    );
+                }
+
+                if ui
+                    .add_enabled(self.states.len() > 1, egui::Button::new("Annulla mossa"))
+                    .clicked()
+                {
+                    self.undo();
+                }
+                if ui
+                    .add_enabled(
+                        !self.redo_stack.is_empty(),
+                        egui::Button::new("Ripeti mossa"),
+                    )
+                    .clicked()
+                {
+                    self.redo();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Seme:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.seed_input)
+                        .hint_text("casuale")
+                        .desired_width(140.0),
+                )
+                .on_hover_text(
+                    "Lo stesso seme, con la stessa percentuale, dà la stessa partita a caso",
+                );
+                if !self.seed_input.trim().is_empty()
+                    && self.seed_input.trim().parse::<u64>().is_err()
+                {
+                    ui.colored_label(egui::Color32::RED, "non è un numero");
+                }
+            });
+            if let Some(reproducer::Start::Random { seed, fill }) = self.start {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "Seme di questa partita: {seed} ({:.0}%)",
+                        fill * 100.0
+                    ));
+                    if ui.small_button("Copia").clicked() {
+                        ui.ctx().copy_text(seed.to_string());
+                    }
+                });
+            }
+            // a search already running still lands, and the game carries on from there
+            if (self.playing_for_me || self.autoplaying) && ui.button("Ferma").clicked() {
+                self.playing_for_me = false;
+                self.autoplaying = false;
+                self.autoplay_next = None;
+            }
+
+            ui.horizontal(|ui| {
+                let can_end = self.human_can_move() && self.draw_offer.is_none();
+                if ui
+                    .add_enabled(can_end, egui::Button::new("Abbandona"))
+                    .clicked()
+                {
+                    self.end_game(GameEnd::Resignation(self.mover()));
+                }
+                if ui
+                    .add_enabled(can_end, egui::Button::new("Proponi patta"))
+                    .clicked()
+                {
+                    // in hotseat the other player decides, the computer accepts only if it
+                    // isn't ahead
+                    if self.hotseat {
+                        info!(mark = %self.mover(), "draw_offered");
+                        self.draw_offer = Some(self.mover());
+                    } else if self.eval() <= 0 {
+                        self.end_game(GameEnd::Draw(DrawReason::Agreed));
+                        self.message = None;
                     } else {
-                        break;
+                        self.message = Some("La CPU rifiuta la patta".to_string());
                     }
                 }
-                let font_size = (left_font_size + right_font_size) / 2.0;
-                ui.label(
-                    egui::RichText::new("Valutazione:").font(egui::FontId::proportional(font_size)),
+            });
+            if let Some(mark) = self.draw_offer {
+                ui.label(format!("{mark} propone la patta a {}", !mark));
+                ui.horizontal(|ui| {
+                    if ui.button("Accetta").clicked() {
+                        self.draw_offer = None;
+                        self.end_game(GameEnd::Draw(DrawReason::Agreed));
+                        self.message = None;
+                    }
+                    if ui.button("Rifiuta").clicked() {
+                        info!(mark = %!mark, "draw_declined");
+                        self.draw_offer = None;
+                        self.message = Some(format!("{} rifiuta la patta", !mark));
+                    }
+                });
+            }
+            if !self.game_over() {
+                ui.label(format!(
+                    "Complessità della posizione: {}",
+                    describe_complexity(self.board().complexity())
+                ))
+                .on_hover_text(
+                    "Più mosse possibili e più minacce aperte fanno pensare di più la CPU",
                 );
-                ui.label(
-                    egui::RichText::new(format!("{}", self.eval()))
-                        .font(egui::FontId::proportional(font_size))
-                        .color(if self.eval() < 0 {
-                            egui::Color32::RED
-                        } else if self.eval() > 0 {
-                            egui::Color32::BLUE
-                        } else {
-                            egui::Color32::YELLOW
-                        }),
+            }
+            if let Some(message) = &self.message {
+                ui.label(message);
+            }
+            if self.game_over() && ui.button("Rivedi animazione").clicked() {
+                self.reveal_started = None;
+            }
+            let mut dismiss_tip = false;
+            if let Some(tip) = &mut self.coaching_tip {
+                ui.group(|ui| {
+                    ui.label(format!(
+                        "Coach: con {} hai perso circa {} punti, c'era di meglio.",
+                        describe_move(tip.played),
+                        tip.loss
+                    ));
+                    if tip.revealed {
+                        ui.label(format!("Mossa migliore: {}", describe_move(tip.best)));
+                    }
+                    ui.horizontal(|ui| {
+                        if !tip.revealed && ui.button("Mostra la mossa migliore").clicked() {
+                            tip.revealed = true;
+                        }
+                        if ui.button("Ok").clicked() {
+                            dismiss_tip = true;
+                        }
+                    });
+                });
+            }
+            if dismiss_tip {
+                self.coaching_tip = None;
+            }
+            if self.computer_set_trap() {
+                ui.colored_label(
+                    egui::Color32::ORANGE,
+                    "⚠ Trappola: ogni tua mossa concede qualcosa alla CPU",
+                )
+                .on_hover_text(
+                    "Qualunque mossa tu faccia, la CPU può vincere una sottotabella o \
+                     crearci una doppia minaccia",
                 );
+            }
+            if self.worker_crashed && !self.thinking && ui.button("Riprova").clicked() {
+                self.worker_crashed = false;
+                self.message = None;
+                self.start_computer_if_its_turn();
+            }
+            if self.settings.touch_mode
+                && self.zoomed.is_some()
+                && ui.button("Torna al tabellone").clicked()
+            {
+                self.zoomed = None;
+            }
+            let untouched = self.states.len() == 1 && self.game_moves.is_empty();
+            if ui
+                .add_enabled(
+                    !self.thinking && untouched && self.sandbox.is_none(),
+                    egui::Button::new("Inizia la CPU"),
+                )
+                .clicked()
+            {
+                self.states[0].to_move = self.computer();
+                self.start_computer_if_its_turn();
+            }
+
+            if ui
+                .add_enabled(
+                    !self.thinking && !self.game_over() && self.sandbox.is_none(),
+                    egui::Button::new("Valuta posizione"),
+                )
+                .clicked()
+            {
+                self.request_analysis();
+            }
+            if let Some(analysis) = &self.analysis
+                && analysis.board == self.board()
+            {
+                ui.label(format!("Analisi per {}:", analysis.player));
+                let shown = |eval| {
+                    self.settings.eval_perspective.apply(
+                        eval,
+                        analysis.player,
+                        analysis.player,
+                        self.human(),
+                    )
+                };
+                match analysis.best() {
+                    Some((r#move, eval)) => {
+                        ui.label(format!("Mossa migliore: {}", describe_move(r#move)));
+                        ui.label(format!("Valutazione: {}", shown(eval)));
+                    }
+                    None => {
+                        ui.label("Nessuna mossa disponibile");
+                    }
+                }
+                for &(r#move, eval) in analysis.top.iter().skip(1) {
+                    ui.label(format!(
+                        "Alternativa: {} ({})",
+                        describe_move(r#move),
+                        shown(eval)
+                    ));
+                }
+                ui.label(format!(
+                    "Nodi: {} in {} ms, profondità {}",
+                    analysis.stats.nodes,
+                    analysis.stats.elapsed.as_millis(),
+                    analysis.stats.depth
+                ));
+            }
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.human_can_move(), egui::Button::new("Suggerimento"))
+                    .on_hover_text("Mostra la mossa che giocherebbe la CPU al tuo posto")
+                    .clicked()
+                {
+                    self.send_request(SearchKind::Hint, self.mover());
+                }
+                if self.hint.is_some() && ui.button("Nascondi suggerimento").clicked() {
+                    self.hint = None;
+                }
             });
+            if ui
+                .add_enabled(self.human_can_move(), egui::Button::new("Miglior difesa"))
+                .on_hover_text("La tua mossa più resistente e come potrebbe continuare")
+                .clicked()
+            {
+                self.send_request(SearchKind::Defense, self.mover());
+            }
+            if let Some(defense) = &self.defense
+                && defense.board == self.board()
+            {
+                match defense.best() {
+                    Some((r#move, eval)) => {
+                        ui.label(format!("Miglior difesa: {}", describe_move(r#move)));
+                        ui.label(format!(
+                            "Valutazione dopo la mossa: {}",
+                            self.settings.eval_perspective.apply(
+                                eval,
+                                defense.player,
+                                !defense.player,
+                                self.human()
+                            )
+                        ));
+                        ui.label(format!("Linea prevista: {}", describe_line(&defense.pv)));
+                    }
+                    None => {
+                        ui.label("Nessuna mossa disponibile");
+                    }
+                }
+            }
+            if self.settings.developer_mode {
+                if ui.button("Copia rapporto di debug").clicked() {
+                    ui.ctx().copy_text(self.debug_report());
+                    info!("debug_report_copied");
+                }
+                let reproducer = self.reproducer();
+                if ui
+                    .add_enabled(
+                        reproducer.is_some(),
+                        egui::Button::new("Copia riproduttore"),
+                    )
+                    .on_disabled_hover_text("La partita è partita da una posizione modificata")
+                    .clicked()
+                    && let Some(reproducer) = reproducer
+                {
+                    ui.ctx().copy_text(reproducer.to_string());
+                    info!("reproducer_copied");
+                }
+                let computer = self.computer();
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.forced_move_input)
+                            .hint_text(format!("{computer}11-22"))
+                            .desired_width(80.0),
+                    );
+                    if ui.button("Forza la prossima mossa della CPU").clicked() {
+                        match self.forced_move_input.trim().parse::<game::Move>() {
+                            Ok(r#move) if r#move.player == computer => {
+                                self.forced_move = Some(r#move);
+                                self.message = None;
+                            }
+                            Ok(_) => self.message = Some(format!("La CPU gioca con {computer}")),
+                            Err(e) => self.message = Some(e.to_string()),
+                        }
+                    }
+                });
+                if let Some(forced) = self.forced_move {
+                    ui.label(format!("Prossima mossa della CPU: {forced}"));
+                }
+                ui.collapsing("Carica riproduttore", |ui| {
+                    ui.text_edit_multiline(&mut self.reproducer_input);
+                    if ui
+                        .add_enabled(!self.thinking, egui::Button::new("Ricostruisci"))
+                        .clicked()
+                    {
+                        match self.reproducer_input.parse() {
+                            Ok(reproducer) => self.load_reproducer(&reproducer),
+                            Err(e) => self.message = Some(format!("Riproduttore non valido: {e}")),
+                        }
+                    }
+                });
+            }
+
+            if self.settings.record_positions {
+                let board = self.board();
+                let db = self
+                    .position_db
+                    .get_or_insert_with(position_db::PositionDb::load);
+                match db.lookup(&board) {
+                    Some(entry) => {
+                        let results = entry.results;
+                        ui.label(format!(
+                            "Posizione vista {} volte (X: {}, O: {}, patte: {})",
+                            entry.seen, results.x_wins, results.o_wins, results.draws
+                        ));
+                        for (r#move, results) in entry.moves.iter().take(3) {
+                            ui.label(format!(
+                                "{}: {} volte",
+                                describe_move(*r#move),
+                                results.x_wins + results.o_wins + results.draws
+                            ));
+                        }
+                    }
+                    None => {
+                        ui.label("Posizione mai vista");
+                    }
+                }
+            }
+
+            ui.separator();
+
+            if self.game_over() {
+                ui.label("Partita finita");
+            } else {
+                let to_move = self.to_move();
+                if self.hotseat {
+                    ui.label(format!("Tocca a: {to_move}"));
+                } else {
+                    ui.label(format!(
+                        "Tocca a: {to_move} ({})",
+                        if to_move == self.human() { "tu" } else { "CPU" }
+                    ));
+                }
+            }
+            if self.clock.is_some() {
+                ui.horizontal(|ui| {
+                    for mark in [game::Mark::X, game::Mark::O] {
+                        let remaining = format_clock(self.remaining(mark));
+                        let text = egui::RichText::new(format!("{mark} {remaining}")).monospace();
+                        if mark == self.to_move() && !self.game_over() {
+                            ui.label(text.strong());
+                        } else {
+                            ui.label(text);
+                        }
+                    }
+                });
+            }
+
+            ui.separator();
+
+            let (bar_rect, _) = ui.allocate_exact_size(
+                egui::vec2(ui.available_width(), SIDE_EVAL_BAR_HEIGHT),
+                egui::Sense::hover(),
+            );
+            let x_share = self.x_win_probability();
+            draw_eval_bar(ui, bar_rect, x_share, self.shown_eval());
+            let color = if x_share > 0.5 {
+                egui::Color32::RED
+            } else if x_share < 0.5 {
+                egui::Color32::BLUE
+            } else {
+                egui::Color32::YELLOW
+            };
+            ui.label(format!(
+                "X: {:.0}% / O: {:.0}%",
+                x_share * 100.0,
+                (1.0 - x_share) * 100.0
+            ));
+            ui.label(
+                egui::RichText::new(format!("Valutazione: {}", self.shown_eval())).color(color),
+            );
+
+            if !self.game_moves.is_empty() {
+                match self.reviewing {
+                    Some(index) => {
+                        ui.horizontal(|ui| {
+                            ui.label(format!(
+                                "Analisi della partita: {}/{}",
+                                index + 1,
+                                self.game_moves.len()
+                            ));
+                            if ui.button("Interrompi").clicked() {
+                                self.reviewing = None;
+                            }
+                        });
+                        ui.add(egui::ProgressBar::new(
+                            index as f32 / self.game_moves.len() as f32,
+                        ));
+                    }
+                    None => {
+                        if ui
+                            .add_enabled(
+                                !self.thinking && self.sandbox.is_none(),
+                                egui::Button::new("Analizza partita"),
+                            )
+                            .on_hover_text("Confronta ogni mossa con quella migliore")
+                            .clicked()
+                        {
+                            self.review_move(0);
+                        }
+                    }
+                }
+                ui.collapsing("Mosse della partita", |ui| {
+                    egui::ScrollArea::vertical()
+                        .max_height(200.0)
+                        .show(ui, |ui| {
+                            let mut previous_eval = 0;
+                            for (i, played) in self.game_moves.iter().enumerate() {
+                                let blunder = is_blunder(
+                                    played.r#move.player == self.computer(),
+                                    previous_eval,
+                                    played.eval,
+                                    self.settings.blunder_threshold,
+                                );
+                                previous_eval = played.eval;
+                                ui.horizontal(|ui| {
+                                    ui.label(format!(
+                                        "{}. {} {} ({})",
+                                        i + 1,
+                                        played.r#move.player,
+                                        describe_move(played.r#move),
+                                        self.settings.eval_perspective.apply(
+                                            played.eval,
+                                            self.computer(),
+                                            !played.r#move.player,
+                                            self.human()
+                                        )
+                                    ));
+                                    if blunder {
+                                        ui.colored_label(egui::Color32::ORANGE, "⚠")
+                                            .on_hover_text("Probabile errore grave");
+                                    }
+                                    if let Some(annotation) = played.annotation {
+                                        let (symbol, color) = annotation.symbol();
+                                        ui.colored_label(color, symbol).on_hover_text(format!(
+                                            "Giocata: {}, migliore: {} ({})",
+                                            annotation.played_eval,
+                                            annotation.best,
+                                            annotation.best_eval
+                                        ));
+                                    }
+                                });
+                            }
+                            let last = self.states.last().unwrap();
+                            if let Some(end) = last.end {
+                                ui.strong(end.describe(last.variant));
+                            }
+                        });
+                });
+                self.show_history(ui);
+            }
+
+            if self.last_computer_move().is_some() && self.to_move() == self.human() {
+                ui.collapsing("Perché questa mossa?", |ui| {
+                    let breakdown = game::searcher::Searcher::heuristic_breakdown(
+                        &self.board(),
+                        self.computer(),
+                        self.human(),
+                        self.variant(),
+                        &self.weights(),
+                        self.draw_score(),
+                    );
+                    egui::Grid::new("breakdown_grid").show(ui, |ui| {
+                        for (label, value) in breakdown_rows(&breakdown) {
+                            ui.label(label);
+                            ui.label(value.to_string());
+                            ui.end_row();
+                        }
+                        ui.strong("Totale");
+                        ui.strong(breakdown.total().to_string());
+                        ui.end_row();
+                    });
+                });
+            }
 
             ui.separator();
 
@@ -271,20 +2639,71 @@ impl eframe::App for App {
                     / 1024.0)
             ));
 
+            let id = self.game_id();
+            ui.label(format!("ID partita: {id}"));
             ui.horizontal(|ui| {
                 if ui.button("Salva").clicked()
                     && let Some(path) = rfd::FileDialog::new()
                         .set_title("Salva partita")
-                        .set_file_name("supertris_save.bin")
+                        .set_file_name(format!("supertris_{id}.bin"))
                         .save_file()
                 {
-                    bincode::encode_into_std_write(
-                        &self.states,
-                        &mut std::fs::File::create(&path).unwrap(),
-                        bincode::config::standard(),
-                    )
-                    .unwrap();
-                    info!(path = %path.display(), "game_saved");
+                    match std::fs::write(&path, encode_save(&self.states)) {
+                        Ok(()) => info!(
+                            path = %path.display(),
+                            ply = self.game_moves.len(),
+                            id,
+                            "game_saved"
+                        ),
+                        Err(e) => {
+                            error!(error = ?e, path = %path.display(), "game_save_error");
+                            self.message = Some(format!("Impossibile salvare la partita: {e}"));
+                        }
+                    }
+                }
+                if ui
+                    .button("Esporta mosse")
+                    .on_hover_text("Scrive le mosse e le valutazioni in un file JSON leggibile")
+                    .clicked()
+                    && let Some(path) = rfd::FileDialog::new()
+                        .set_title("Esporta mosse")
+                        .set_file_name(format!("supertris_{id}.json"))
+                        .add_filter("JSON", &["json"])
+                        .save_file()
+                {
+                    let transcript = export::transcript(
+                        self.game_moves
+                            .iter()
+                            .map(|played| (played.r#move, played.eval)),
+                    );
+                    let written = serde_json::to_string_pretty(&transcript)
+                        .map_err(color_eyre::Report::from)
+                        .and_then(|json| Ok(std::fs::write(&path, json)?));
+                    match written {
+                        Ok(()) => info!(path = %path.display(), id, "moves_exported"),
+                        Err(e) => error!(error = ?e, path = %path.display(), "moves_export_error"),
+                    }
+                }
+                if ui
+                    .add_enabled(!self.thinking, egui::Button::new("Importa mosse"))
+                    .on_hover_text("Riprende una partita scritta da \"Esporta mosse\"")
+                    .clicked()
+                    && let Some(path) = rfd::FileDialog::new()
+                        .set_title("Importa mosse")
+                        .add_filter("JSON", &["json"])
+                        .pick_file()
+                {
+                    self.import_moves(&path);
+                }
+                if ui
+                    .add_enabled(!self.thinking, egui::Button::new("Incolla partita"))
+                    .on_hover_text("Ricostruisce la partita dalle mosse negli appunti")
+                    .clicked()
+                {
+                    ui.ctx()
+                        .send_viewport_cmd(egui::ViewportCommand::RequestPaste);
+                    self.awaiting_paste = Some(ui.input(|i| i.time));
+                    ui.ctx().request_repaint();
                 }
                 if ui.button("Carica").clicked()
                     && let Some(path) = rfd::FileDialog::new()
@@ -293,127 +2712,547 @@ impl eframe::App for App {
                         .add_filter("Binary save file", &["bin"])
                         .pick_file()
                 {
-                    let rfp = self.random_fill_percentage;
-                    *self = App::default();
-                    self.random_fill_percentage = rfp;
-                    self.states = bincode::decode_from_std_read(
-                        &mut std::fs::File::open(&path).unwrap(),
-                        bincode::config::standard(),
-                    )
-                    .unwrap_or_else(|e| {
-                        error!(error = ?e, "save_load_error");
-                        vec![]
-                    });
-                    info!(path = %path.display(), "game_loaded");
+                    // a broken or empty save leaves the current game alone
+                    match std::fs::read(&path)
+                        .map_err(|e| format!("impossibile leggere il file: {e}"))
+                        .and_then(|bytes| decode_save(&bytes).map_err(|e| e.to_string()))
+                    {
+                        Ok(states) if !states.is_empty() => {
+                            self.restart();
+                            self.start = None;
+                            self.viewing = None;
+                            self.states = states;
+                            self.rebuild_game_moves();
+                            self.restore_clocks();
+                            info!(
+                                path = %path.display(),
+                                ply = self.game_moves.len(),
+                                id = self.game_id(),
+                                "game_loaded"
+                            );
+                            self.start_computer_if_its_turn();
+                        }
+                        Ok(_) => {
+                            error!(path = %path.display(), "save_load_empty");
+                            self.load_error = Some("il file non contiene nessuna partita".into());
+                        }
+                        Err(e) => {
+                            error!(error = ?e, "save_load_error");
+                            self.load_error = Some(e);
+                        }
+                    }
                 }
             });
         });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            draw_game(ui, self);
-        });
-    }
+        egui::CentralPanel::default().show(ctx, |ui| match &mut self.editor {
+            Some(editor) => editor.draw(ui, &self.settings.style, GridHelper::new(ui.max_rect())),
+            None => draw_game(ui, self),
+        });
+        self.record_finished_game();
+
+        if let Some(step) = self.tutorial_step {
+            self.tutorial_step = tutorial::show(ctx, &self.settings.style, step);
+            if self.tutorial_step.is_none() {
+                self.settings.tutorial_seen = true;
+            }
+        }
+
+        if let Some(load_error) = &self.load_error {
+            let mut close = false;
+            let modal = egui::Modal::new("load_error_modal".into()).show(ctx, |ui| {
+                ui.heading("Impossibile caricare la partita");
+                ui.label(load_error);
+                ui.separator();
+                close = ui.button("Chiudi").clicked();
+            });
+            if close || modal.should_close() {
+                self.load_error = None;
+            }
+        }
+
+        if self.settings_open {
+            let modal = egui::Modal::new("settings_modal".into()).show(ctx, |ui| {
+                self.settings.show(ui);
+                ui.separator();
+                if ui.button("Chiudi").clicked() {
+                    self.settings_open = false;
+                }
+            });
+            if modal.should_close() {
+                self.settings_open = false;
+            }
+        }
+    }
+}
+
+// evals are from the computer's side, so a rising eval is a drop for the human
+fn is_blunder(by_computer: bool, before: i32, after: i32, threshold: i32) -> bool {
+    eval_drop(by_computer, before, after) > threshold as i64
+}
+
+// how much a move made things worse for its mover, from two evals in the computer's view
+fn eval_drop(by_computer: bool, before: i32, after: i32) -> i64 {
+    let swing = after as i64 - before as i64;
+    if by_computer { -swing } else { swing }
+}
+
+fn breakdown_rows(breakdown: &game::searcher::HeuristicBreakdown) -> Vec<(&'static str, i32)> {
+    if let Some(terminal) = breakdown.terminal {
+        return vec![("Partita decisa", terminal)];
+    }
+    vec![
+        ("Controllo tabellone", breakdown.meta_control),
+        ("Minacce tabellone", breakdown.meta_threats),
+        ("Sottotabelle vinte", breakdown.subboard_wins),
+        ("Minacce", breakdown.threats),
+        ("Centri", breakdown.center),
+        ("Lati", breakdown.edges),
+        ("Angoli", breakdown.corners),
+        ("Scelta libera", breakdown.free_choice),
+        ("Vittoria impossibile", breakdown.unwinnable),
+    ]
+}
+
+const EVAL_BAR_WIDTH: f32 = 16.0;
+// how long "Incolla partita" waits for the clipboard before giving up on it
+const PASTE_TIMEOUT_SECONDS: f64 = 1.0;
+const EVAL_CHART_HEIGHT: f32 = 60.0;
+// how long the winner or draw reveal takes before it comes to rest
+const REVEAL_SECONDS: f64 = 1.0;
+// the eval at which the side it's for is taken to win about three games out of four. Fitted
+// to self-play at depth 2 from random openings (the calibration line of `supertris bench`):
+// the log loss is flat from about 3000 to 6000 and climbs quickly below 2000, so this is the
+// low end of the flat part, where the bar still moves
+const EVAL_SCALE: f64 = 3000.0;
+const SIDE_EVAL_BAR_HEIGHT: f32 = 20.0;
+
+// how likely the side `eval` is for is to win; the terminal scores land on the ends instead
+// of stretching the scale
+fn win_probability(eval: i32) -> f64 {
+    win_probability_at(eval, EVAL_SCALE)
+}
+
+fn win_probability_at(eval: i32, scale: f64) -> f64 {
+    match eval {
+        i32::MAX => 1.0,
+        i32::MIN => 0.0,
+        _ => 1.0 / (1.0 + (-eval as f64 / scale * 3f64.ln()).exp()),
+    }
+}
+
+// X's share fills in red from the bottom, or from the left when the bar lies flat, and O's in
+// blue from the other end; `shown` is the eval the hover reads out
+fn draw_eval_bar(ui: &mut egui::Ui, rect: egui::Rect, x_share: f64, shown: i32) {
+    let x_share = x_share as f32;
+    let (o, x, middle) = if rect.height() > rect.width() {
+        let (o, x) = rect.split_top_bottom_at_y(rect.bottom() - rect.height() * x_share);
+        (o, x, [rect.left_center(), rect.right_center()])
+    } else {
+        let (x, o) = rect.split_left_right_at_x(rect.left() + rect.width() * x_share);
+        (o, x, [rect.center_top(), rect.center_bottom()])
+    };
+    ui.painter().rect_filled(o, 0.0, egui::Color32::BLUE);
+    ui.painter().rect_filled(x, 0.0, egui::Color32::RED);
+    ui.painter()
+        .line_segment(middle, egui::Stroke::new(1.0, egui::Color32::YELLOW));
+    ui.interact(rect, ui.id().with("eval_bar"), egui::Sense::hover())
+        .on_hover_text(format!("Valutazione: {shown}"));
+}
+
+// one point per state, high when the computer is winning, with a line at `marker`; says which
+// state was clicked
+fn draw_eval_chart(ui: &mut egui::Ui, evals: &[i32], marker: usize) -> Option<usize> {
+    let size = egui::vec2(ui.available_width(), EVAL_CHART_HEIGHT);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    painter.hline(
+        rect.x_range(),
+        rect.center().y,
+        egui::Stroke::new(1.0, egui::Color32::GRAY),
+    );
+
+    let step = rect.width() / (evals.len().max(2) - 1) as f32;
+    let x = |i: usize| rect.left() + i as f32 * step;
+    let points = evals
+        .iter()
+        .enumerate()
+        .map(|(i, &eval)| {
+            egui::pos2(
+                x(i),
+                rect.bottom() - rect.height() * win_probability(eval) as f32,
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, egui::Color32::BLUE),
+    ));
+    painter.vline(
+        x(marker),
+        rect.y_range(),
+        egui::Stroke::new(2.0, egui::Color32::YELLOW),
+    );
+
+    let pointer = response
+        .interact_pointer_pos()
+        .filter(|_| response.clicked())?;
+    let clicked = ((pointer.x - rect.left()) / step).round() as usize;
+    Some(clicked.min(evals.len() - 1))
+}
+
+// tucked in the corner of the board, which stays visible; clicks on it are already ignored
+// while `thinking`
+fn draw_thinking_indicator(ui: &egui::Ui, app: &App) {
+    let complexity = describe_complexity(app.states.last().unwrap().board.complexity());
+    egui::Area::new("thinking_indicator".into())
+        .order(egui::Order::Foreground)
+        .fixed_pos(ui.max_rect().left_top() + egui::vec2(8.0, 8.0))
+        .interactable(false)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    // the spinner repaints every frame for as long as it's visible
+                    if !app.settings.power_saver {
+                        ui.spinner();
+                    }
+                    ui.label("Sto pensando…");
+                });
+                ui.small(format!("Complessità: {complexity}"));
+            });
+        });
+}
+
+// a forced move isn't worth a message, it happens all the time
+fn describe_shortcut(
+    shortcut: game::searcher::Shortcut,
+    by_computer: bool,
+) -> Option<&'static str> {
+    use game::searcher::Shortcut;
+    Some(match (shortcut, by_computer) {
+        (Shortcut::Win, true) => "La CPU ha visto la vittoria al volo!",
+        (Shortcut::Win, false) => "Mossa vincente, partita chiusa!",
+        (Shortcut::OnlyDefense, true) => {
+            "La CPU ha parato l'unica minaccia che ti dava la vittoria"
+        }
+        (Shortcut::OnlyDefense, false) => "Era l'unica mossa per non perdere subito",
+        (Shortcut::OnlyMove, _) => return None,
+    })
+}
+
+// mm:ss, rounded up so a clock only shows 00:00 once it has actually run out
+fn format_clock(remaining: std::time::Duration) -> String {
+    let seconds = remaining.as_millis().div_ceil(1000);
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn describe_complexity(complexity: usize) -> String {
+    let level = match complexity {
+        0..20 => "bassa",
+        20..50 => "media",
+        _ => "alta",
+    };
+    format!("{level} ({complexity})")
 }
 
 fn draw_game(ui: &mut egui::Ui, app: &mut App) {
-    let gh = GridHelper::new(ui.max_rect());
+    let mut board_rect = ui.max_rect();
+    if app.settings.eval_bar {
+        let bar_rect = board_rect.with_min_x(board_rect.max.x - EVAL_BAR_WIDTH);
+        board_rect.max.x -= EVAL_BAR_WIDTH * 1.5;
+        draw_eval_bar(ui, bar_rect, app.x_win_probability(), app.shown_eval());
+    }
+    let gh = GridHelper::new(board_rect);
+    let style = app.settings.style;
 
     if app.thinking {
-        if let Ok(computer_move) = app.resp_rx.try_recv() {
-            if let Some((r#move, eval)) = computer_move {
-                let _span = tracing::debug_span!("computer_move", "move" = ?r#move, eval).entered();
-                if let Some(new_board) = app.board().make_move(r#move) {
-                    info!("computer_move_done");
-
-                    let is_fake_human = r#move.player != game::COMPUTER_MARK;
-
-                    if is_fake_human {
-                        let mut new_state = app
-                            .states
-                            .last()
-                            .copied()
-                            .unwrap_or_else(|| GameState::root(game::OuterBoard::default()));
-
-                        new_state.last_player_move = Some(r#move);
-                        new_state.board = new_board;
-                        new_state.eval = eval;
-
-                        app.states.push(new_state);
-                        app.req_tx.send((game::COMPUTER_MARK, app.board())).unwrap();
-                        app.thinking = true;
-                    } else {
-                        if app.states.is_empty() {
-                            app.states.push(GameState::default());
-                        }
-                        let state = app.states.last_mut().unwrap();
-                        state.last_computer_move = Some(r#move);
-                        state.board = new_board;
-                        state.eval = eval;
-                        app.thinking = false;
-                    }
-                } else {
-                    error!("computer_move_invalid");
-                    app.thinking = false;
+        let response = match app.injected_move.take() {
+            Some(r#move) => {
+                // checked against the latest state, whatever is being looked at
+                let latest = app.states.last().unwrap();
+                let eval = game::searcher::Searcher::heuristic(
+                    &latest.board.make_move(r#move).unwrap(),
+                    latest.computer(),
+                    !r#move.player,
+                    app.variant(),
+                    &app.weights(),
+                    app.draw_score(),
+                );
+                let stats = game::searcher::SearchStats {
+                    nodes: 0,
+                    depth: 0,
+                    elapsed: std::time::Duration::ZERO,
+                    shortcut: None,
+                    timed_out: false,
+                };
+                Ok(SearchResponse::Move(Some((r#move, eval)), stats))
+            }
+            None => app.resp_rx.try_recv(),
+        };
+        match response {
+            Ok(SearchResponse::Analysis(analysis)) => {
+                info!(nodes = analysis.stats.nodes, "analysis_done");
+                app.analysis = Some(analysis);
+                app.thinking = false;
+            }
+            Ok(SearchResponse::Hint(hint)) => {
+                info!("move" = ?hint, "hint_found");
+                app.hint = hint;
+                app.thinking = false;
+            }
+            Ok(SearchResponse::Defense(analysis)) => {
+                info!(nodes = analysis.stats.nodes, "defense_analysis_done");
+                app.defense = Some(analysis);
+                app.thinking = false;
+            }
+            Ok(SearchResponse::Review(analysis)) => {
+                app.thinking = false;
+                // a cancelled review still gets the ply it was on, but doesn't go on
+                if let Some(index) = app.reviewing.take()
+                    && let Some(played) = app.game_moves.get_mut(index)
+                {
+                    played.annotation =
+                        Annotation::new(&analysis, played.r#move, app.settings.blunder_threshold);
+                    app.review_move(index + 1);
                 }
-            } else {
-                error!("no_computer_move");
+            }
+            Ok(SearchResponse::Coaching(analysis)) => {
+                info!(nodes = analysis.stats.nodes, "coaching_analysis_done");
+                app.coaching_analysis = Some(analysis);
                 app.thinking = false;
             }
-        } else {
-            egui::Modal::new("thinking_modal".into()).show(ui.ctx(), |ui| {
-                ui.vertical_centered(|ui| {
-                    ui.label("Thinking...");
-                    ui.spinner();
-                });
-            });
+            Ok(SearchResponse::Move(computer_move, stats)) => {
+                app.apply_engine_move(computer_move, stats)
+            }
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => app.restart_worker(),
+            Err(std::sync::mpsc::TryRecvError::Empty) if app.thinking_quietly => {}
+            Err(std::sync::mpsc::TryRecvError::Empty) => {
+                draw_thinking_indicator(ui, app);
+            }
+        }
+    }
+
+    let player_move = match app.zoomed.filter(|_| app.settings.touch_mode) {
+        Some((row, col)) => draw_zoomed_subboard(ui, app, &style, gh, row, col),
+        None => draw_board(ui, app, &style, gh),
+    };
+
+    if app.sandbox.is_some()
+        && app.can_place()
+        && let Some(r#move) = player_move
+        && let Some(new_board) = app.board().make_move(r#move)
+    {
+        info!(mover = %r#move.player, "move" = %r#move, "sandbox_move_made");
+        app.zoomed = None;
+        app.place_sandbox_mark(r#move, new_board);
+    } else if app.human_can_move_here()
+        && let Some(player_move) = player_move
+        && let Some(new_board) = app.board().make_move(player_move)
+    {
+        app.branch_from_displayed();
+        app.zoomed = None;
+        let old_board = app.board();
+        let mut new_state = *app.state();
+        new_state.board = new_board;
+        new_state.last_player_move = Some(player_move);
+        new_state.to_move = !player_move.player;
+        new_state.eval = game::searcher::Searcher::heuristic(
+            &new_state.board,
+            new_state.computer(),
+            new_state.computer(),
+            new_state.variant,
+            &app.weights(),
+            app.draw_score(),
+        );
+        new_state.settle_end();
+        app.states.push(new_state);
+        app.log_move(old_board, player_move, None);
+        app.message = None;
+        app.coaching_tip = None;
+        app.hint = None;
+        // moving on instead of answering turns the offer down
+        app.draw_offer = None;
+        app.coach_move(old_board, player_move);
+
+        if !app.game_over() && !app.hotseat {
+            app.request_search(app.computer());
+        }
+    }
+    app.request_coaching_if_needed();
+
+    if app.settings.show_computer_target
+        && app.thinking
+        && !app.thinking_quietly
+        && app.to_move() == app.computer()
+        && let Some((row, col)) = app.board().active_square
+    {
+        draw_computer_target(ui.ctx(), &style, gh.subgrid(row, col));
+    }
+
+    if app.settings.heatmap != settings::Heatmap::Off {
+        let counts = app.cell_counts();
+        draw_heatmap(ui, gh, &counts);
+    }
+
+    if app.settings.analysis_arrows
+        && let Some(analysis) = &app.analysis
+        && analysis.board == app.board()
+    {
+        draw_analysis_arrows(ui, &style, gh, &analysis.top);
+    }
+
+    let drawn = app.game_over() && app.overall_winner().is_none();
+    if app.overall_winner().is_none() && !drawn {
+        app.reveal_started = None;
+        return;
+    }
+
+    let now = ui.ctx().input(|i| i.time);
+    let started = *app.reveal_started.get_or_insert(now);
+    let t = if app.settings.power_saver {
+        1.0
+    } else {
+        ((now - started) / REVEAL_SECONDS).clamp(0.0, 1.0) as f32
+    };
+    // once it has settled nothing moves any more, so there's nothing left to repaint for
+    if t < 1.0 {
+        ui.ctx().request_repaint();
+    }
+
+    draw_obscuring_square(ui, gh);
+    match app.overall_winner() {
+        Some(winner) => {
+            // grows a bit past its final size, then settles back
+            const OVERSHOOT: f32 = 1.70158;
+            let eased = 1.0 + (OVERSHOOT + 1.0) * (t - 1.0).powi(3) + OVERSHOOT * (t - 1.0).powi(2);
+            draw_filled_square(
+                ui.painter(),
+                &style,
+                gh.rect.center().x,
+                gh.rect.center().y,
+                gh.rect.width() / 2.0 * 0.85 * eased,
+                winner,
+                false,
+            );
         }
+        None => {
+            // fades in while dropping into place
+            let offset = (1.0 - t) * gh.rect.height() / 8.0;
+            ui.painter().text(
+                gh.rect.center() - egui::vec2(0.0, offset),
+                egui::Align2::CENTER_CENTER,
+                "Patta",
+                egui::FontId::proportional(gh.rect.width() / 6.0),
+                egui::Color32::YELLOW.gamma_multiply(t),
+            );
+        }
+    }
+    if let Some(end) = app.end() {
+        ui.painter().text(
+            gh.rect.center_bottom() - egui::vec2(0.0, gh.rect.height() / 12.0),
+            egui::Align2::CENTER_CENTER,
+            end.describe(app.variant()),
+            egui::FontId::proportional(gh.rect.width() / 18.0),
+            ui.visuals().strong_text_color().gamma_multiply(t),
+        );
     }
+}
 
+fn draw_board(
+    ui: &mut egui::Ui,
+    app: &mut App,
+    style: &settings::BoardStyle,
+    gh: GridHelper,
+) -> Option<game::Move> {
     draw_grid_lines(
         ui,
+        style,
         gh,
         app.overall_winner().is_none() && app.board().active_square.is_none(),
     );
 
     let mut player_move = None;
+    let legal = if app.settings.legal_move_hints && app.human_can_move() {
+        app.board().possible_moves(app.mover())
+    } else {
+        Default::default()
+    };
 
     for row in 0..3 {
         for col in 0..3 {
             let inner_board = &mut app.board().boards[row as usize][col as usize];
             let sub_gh = gh.subgrid(row, col);
 
-            draw_grid_lines(ui, sub_gh, app.board().active_square == Some((row, col)));
+            if app.settings.threat_tint && inner_board.can_play() {
+                draw_threat_tint(ui, sub_gh, inner_board);
+            }
+            draw_grid_lines(
+                ui,
+                style,
+                sub_gh,
+                app.board().active_square == Some((row, col)),
+            );
 
             for inner_row in 0..3 {
                 for inner_col in 0..3 {
-                    if draw_grid_item(
+                    let square = inner_board.squares[inner_row as usize][inner_col as usize];
+                    let candidate = game::Move {
+                        outer: (row, col),
+                        inner: (inner_row, inner_col),
+                        player: app.placing_mark(),
+                    };
+                    let playable = app.can_place() && app.board().make_move(candidate).is_some();
+                    if legal.contains(&candidate) {
+                        draw_legal_hint(ui, style, sub_gh.subgrid(inner_row, inner_col));
+                    }
+                    if app.hint == Some(candidate) && app.human_can_move() {
+                        draw_suggested_cell(ui, sub_gh.subgrid(inner_row, inner_col));
+                    }
+                    let response = draw_grid_item(
                         ui,
+                        style,
                         sub_gh,
                         inner_row,
                         inner_col,
-                        inner_board.squares[inner_row as usize][inner_col as usize],
-                        app.overall_winner().is_none()
-                            && (app.last_computer_move().is_some_and(|m| {
-                                m.outer == (row, col) && m.inner == (inner_row, inner_col)
-                            }) || app.last_player_move().is_some_and(|m| {
-                                m.outer == (row, col) && m.inner == (inner_row, inner_col)
-                            })),
-                    )
-                    .clicked()
+                        square,
+                        app.is_last_move((row, col), (inner_row, inner_col)),
+                    );
+                    response.widget_info(|| {
+                        egui::WidgetInfo::labeled(
+                            egui::WidgetType::Button,
+                            playable,
+                            cell_label(candidate, square, playable),
+                        )
+                    });
+                    if response.clicked() && !app.settings.touch_mode {
+                        player_move = Some(candidate);
+                    }
+                    if playable
+                        && app.settings.trap_warnings
+                        && app.sandbox.is_none()
+                        && app.variant() == game::Variant::Standard
+                        && response.hovered()
+                        && let Some(trap) = app.board().make_move(candidate).and_then(|child| {
+                            game::searcher::Searcher::trap(&child, !candidate.player)
+                        })
                     {
-                        player_move = Some(game::Move {
-                            outer: (row, col),
-                            inner: (inner_row, inner_col),
-                            player: game::HUMAN_MARK,
-                        });
+                        ui.painter().rect_stroke(
+                            response.rect,
+                            3.0,
+                            egui::Stroke::new(2.0, egui::Color32::ORANGE),
+                            egui::StrokeKind::Inside,
+                        );
+                        response.on_hover_text(describe_trap(trap));
                     }
                 }
             }
 
             if let Some(winner) = inner_board.winner {
                 draw_obscuring_square(ui, sub_gh);
-                draw_grid_item(
+                let response = draw_grid_item(
                     ui,
+                    style,
                     gh,
                     row,
                     col,
@@ -426,56 +3265,234 @@ fn draw_game(ui: &mut egui::Ui, app: &mut App) {
                                 .last_player_move()
                                 .is_some_and(|m| m.outer == (row, col))),
                 );
-            } else if !inner_board.can_play() {
-                draw_obscuring_square(ui, sub_gh);
+                response.widget_info(|| {
+                    egui::WidgetInfo::labeled(
+                        egui::WidgetType::Label,
+                        false,
+                        format!(
+                            "Sottotabella riga {} colonna {}, vinta da {winner}",
+                            row + 1,
+                            col + 1
+                        ),
+                    )
+                });
+            } else if inner_board.is_drawn() {
+                draw_drawn_subboard(ui, style, sub_gh);
+                ui.interact(
+                    sub_gh.rect,
+                    ui.id().with(("drawn", row, col)),
+                    egui::Sense::hover(),
+                )
+                .widget_info(|| {
+                    egui::WidgetInfo::labeled(
+                        egui::WidgetType::Label,
+                        false,
+                        format!(
+                            "Sottotabella riga {} colonna {}, pareggiata",
+                            row + 1,
+                            col + 1
+                        ),
+                    )
+                });
+            }
+
+            if app.settings.touch_mode {
+                let response = ui.interact(
+                    sub_gh.rect,
+                    ui.id().with(("subboard", row, col)),
+                    egui::Sense::click(),
+                );
+                if response.clicked()
+                    && app.can_place()
+                    && app
+                        .board()
+                        .legal_cells()
+                        .iter()
+                        .any(|&(outer, _)| outer == (row, col))
+                {
+                    app.zoomed = Some((row, col));
+                }
             }
         }
     }
 
-    if !app.thinking
-        && let Some(player_move) = player_move
-        && let Some(new_board) = app.board().make_move(player_move)
-    {
-        info!("move" = ?player_move, "player_move_done");
-        let mut new_state = app
-            .states
-            .last()
-            .copied()
-            .unwrap_or_else(|| GameState::root(game::OuterBoard::default()));
-        new_state.board = new_board;
-        new_state.last_player_move = Some(player_move);
-        new_state.eval = game::searcher::Searcher::heuristic(
-            &new_state.board,
-            game::COMPUTER_MARK,
-            game::COMPUTER_MARK,
-        );
-        app.states.push(new_state);
+    player_move
+}
+
+fn draw_zoomed_subboard(
+    ui: &mut egui::Ui,
+    app: &mut App,
+    style: &settings::BoardStyle,
+    gh: GridHelper,
+    row: u8,
+    col: u8,
+) -> Option<game::Move> {
+    let inner_board = app.board().boards[row as usize][col as usize];
+    draw_grid_lines(ui, style, gh, app.board().active_square == Some((row, col)));
+
+    let mut player_move = None;
+    for inner_row in 0..3 {
+        for inner_col in 0..3 {
+            let square = inner_board.squares[inner_row as usize][inner_col as usize];
+            let center = gh.position(inner_row, inner_col);
+            if let Some(square) = square {
+                draw_filled_square(
+                    ui.painter(),
+                    style,
+                    center.x,
+                    center.y,
+                    gh.square_size() / 2.0 * 0.85,
+                    square,
+                    app.is_last_move((row, col), (inner_row, inner_col)),
+                );
+            }
 
-        if app.overall_winner().is_none() {
-            app.req_tx.send((game::COMPUTER_MARK, app.board())).unwrap();
-            app.thinking = true;
+            let candidate = game::Move {
+                outer: (row, col),
+                inner: (inner_row, inner_col),
+                player: app.placing_mark(),
+            };
+            let playable = app.can_place() && app.board().make_move(candidate).is_some();
+            // the whole cell is the hit target, not just the mark
+            let response = ui.interact(
+                egui::Rect::from_center_size(center, egui::Vec2::splat(gh.square_size())),
+                ui.id().with(("zoomed_cell", inner_row, inner_col)),
+                egui::Sense::click(),
+            );
+            response.widget_info(|| {
+                egui::WidgetInfo::labeled(
+                    egui::WidgetType::Button,
+                    playable,
+                    cell_label(candidate, square, playable),
+                )
+            });
+            if response.clicked() {
+                player_move = Some(candidate);
+            }
         }
     }
 
-    if app.overall_winner().is_some() {
-        let t = (ui.ctx().input(|i| i.time).sin() + 1.0) / 2.0;
-        let scale = (0.85 - 0.5) * t as f32 + 0.5;
-
+    if inner_board.is_drawn() {
+        draw_drawn_subboard(ui, style, gh);
+    } else if !inner_board.can_play() {
         draw_obscuring_square(ui, gh);
-        draw_filled_square(
-            ui.painter(),
-            gh.rect.center().x,
-            gh.rect.center().y,
-            gh.rect.width() / 2.0 * scale,
-            app.overall_winner().unwrap(),
-            false,
+    }
+
+    player_move
+}
+
+// the busiest cell gets the full color, cells never played stay transparent
+fn draw_heatmap(ui: &mut egui::Ui, gh: GridHelper, counts: &[[u32; 9]; 9]) {
+    let max = counts.iter().flatten().copied().max().unwrap_or(0);
+    if max == 0 {
+        return;
+    }
+    for row in 0..3 {
+        for col in 0..3 {
+            let sub_gh = gh.subgrid(row, col);
+            for inner_row in 0..3 {
+                for inner_col in 0..3 {
+                    let count = counts[row as usize * 3 + inner_row as usize]
+                        [col as usize * 3 + inner_col as usize];
+                    if count == 0 {
+                        continue;
+                    }
+                    let heat = count as f32 / max as f32;
+                    ui.painter().rect_filled(
+                        egui::Rect::from_center_size(
+                            sub_gh.position(inner_row, inner_col),
+                            egui::Vec2::splat(sub_gh.square_size()),
+                        ),
+                        0.0,
+                        egui::Color32::from_rgba_unmultiplied(255, 96, 0, (heat * 160.0) as u8),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// one arrow per candidate from the board's center, fainter and thinner the worse it is
+fn draw_analysis_arrows(
+    ui: &mut egui::Ui,
+    style: &settings::BoardStyle,
+    gh: GridHelper,
+    top: &[(game::Move, i32)],
+) {
+    let Some(&(_, best)) = top.first() else {
+        return;
+    };
+    let worst = top.last().map_or(best, |&(_, eval)| eval);
+    let origin = gh.rect.center();
+    ui.painter()
+        .circle_filled(origin, style.highlight_thickness, style.highlight_color);
+    for &(r#move, eval) in top {
+        let quality = if best == worst {
+            1.0
+        } else {
+            (eval as f64 - worst as f64) / (best as f64 - worst as f64)
+        } as f32;
+        let weight = 0.3 + 0.7 * quality;
+        let target = gh
+            .subgrid(r#move.outer.0, r#move.outer.1)
+            .position(r#move.inner.0, r#move.inner.1);
+        ui.painter().arrow(
+            origin,
+            target - origin,
+            egui::Stroke::new(
+                style.highlight_thickness * weight,
+                style.highlight_color.gamma_multiply(weight),
+            ),
         );
+    }
+}
+
+fn describe_line(line: &[game::Move]) -> String {
+    if line.is_empty() {
+        return "nessuna".to_string();
+    }
+    line.iter()
+        .map(|r#move| r#move.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
 
-        ui.ctx()
-            .request_repaint_after(std::time::Duration::from_millis(16));
+fn describe_trap(trap: game::searcher::Trap) -> String {
+    let r#move = trap.r#move();
+    match trap {
+        game::searcher::Trap::Subboard(_) => format!(
+            "Attenzione: l'avversario può rispondere con {} e vincere la sottotabella",
+            describe_move(r#move)
+        ),
+        game::searcher::Trap::DoubleThreat(_) => format!(
+            "Attenzione: l'avversario può rispondere con {} e minacciare due tris",
+            describe_move(r#move)
+        ),
     }
 }
 
+fn describe_move(r#move: game::Move) -> String {
+    format!(
+        "sottotabella {},{} casella {},{}",
+        r#move.outer.0 + 1,
+        r#move.outer.1 + 1,
+        r#move.inner.0 + 1,
+        r#move.inner.1 + 1,
+    )
+}
+
+fn cell_label(r#move: game::Move, square: Option<game::Mark>, playable: bool) -> String {
+    format!(
+        "Sottotabella riga {} colonna {}, casella riga {} colonna {}, {}{}",
+        r#move.outer.0 + 1,
+        r#move.outer.1 + 1,
+        r#move.inner.0 + 1,
+        r#move.inner.1 + 1,
+        square.map_or_else(|| "vuota".to_string(), |m| m.to_string()),
+        if playable { ", giocabile" } else { "" },
+    )
+}
+
 fn draw_obscuring_square(ui: &mut egui::Ui, gh: GridHelper) {
     let painter = ui.painter();
     painter.rect(
@@ -487,8 +3504,78 @@ fn draw_obscuring_square(ui: &mut egui::Ui, gh: GridHelper) {
     );
 }
 
+// lighter than the obscuring square and in the highlight color, so it reads as "here" rather
+// than "closed"
+fn draw_legal_hint(ui: &mut egui::Ui, style: &settings::BoardStyle, gh: GridHelper) {
+    ui.painter()
+        .rect_filled(gh.rect, 3.0, style.highlight_color.gamma_multiply(0.15));
+}
+
+// green rather than the highlight color, so it stands out from the legal cells around it
+fn draw_suggested_cell(ui: &mut egui::Ui, gh: GridHelper) {
+    let color = egui::Color32::from_rgb(0x2e, 0xa0, 0x43);
+    ui.painter().rect(
+        gh.rect,
+        3.0,
+        color.gamma_multiply(0.35),
+        egui::Stroke::new(2.0, color),
+        egui::StrokeKind::Inside,
+    );
+}
+
+// a faint fill in the color of whoever could win the subboard with one move; a fill rather than
+// a border so it never gets mistaken for the active subboard highlight
+fn draw_threat_tint(ui: &mut egui::Ui, gh: GridHelper, inner_board: &game::InnerBoard) {
+    let color = match (
+        inner_board.threats(game::Mark::X) > 0,
+        inner_board.threats(game::Mark::O) > 0,
+    ) {
+        (false, false) => return,
+        (true, false) => egui::Color32::from_rgba_unmultiplied(255, 0, 0, 40),
+        (false, true) => egui::Color32::from_rgba_unmultiplied(0, 0, 255, 40),
+        (true, true) => egui::Color32::from_rgba_unmultiplied(160, 0, 200, 40),
+    };
+    ui.painter().rect_filled(gh.rect, 3.0, color);
+}
+
+// drawn above the thinking modal's backdrop, which would otherwise dim it
+fn draw_computer_target(ctx: &egui::Context, style: &settings::BoardStyle, gh: GridHelper) {
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Tooltip,
+        egui::Id::new("computer_target"),
+    ));
+    painter.rect_stroke(
+        gh.rect.expand(style.highlight_thickness),
+        3.0,
+        egui::Stroke::new(style.highlight_thickness * 1.5, style.highlight_color),
+        egui::StrokeKind::Outside,
+    );
+    painter.text(
+        gh.rect.center_top() - egui::vec2(0.0, style.highlight_thickness * 3.0),
+        egui::Align2::CENTER_BOTTOM,
+        "Il computer deve giocare qui",
+        egui::FontId::proportional(16.0),
+        style.highlight_color,
+    );
+}
+
+// full with no winner: greyed out like a won subboard, with a dash instead of the big mark
+fn draw_drawn_subboard(ui: &mut egui::Ui, style: &settings::BoardStyle, gh: GridHelper) {
+    draw_obscuring_square(ui, gh);
+    let center = gh.rect.center();
+    let half_width = gh.rect.width() / 2.0 * 0.6;
+    ui.painter().line_segment(
+        [
+            egui::pos2(center.x - half_width, center.y),
+            egui::pos2(center.x + half_width, center.y),
+        ],
+        egui::Stroke::new(style.mark_thickness * 2.0, egui::Color32::DARK_GRAY),
+    );
+}
+
 fn draw_grid_item(
     ui: &mut egui::Ui,
+    style: &settings::BoardStyle,
     gh: GridHelper,
     row: u8,
     col: u8,
@@ -501,7 +3588,7 @@ fn draw_grid_item(
     let radius = gh.square_size() / 2.0 * 0.85;
 
     if let Some(square) = square {
-        draw_filled_square(painter, x, y, radius, square, highlight);
+        draw_filled_square(painter, style, x, y, radius, square, highlight);
     }
 
     ui.interact(
@@ -513,6 +3600,7 @@ fn draw_grid_item(
 
 fn draw_filled_square(
     painter: &egui::Painter,
+    style: &settings::BoardStyle,
     x: f32,
     y: f32,
     radius: f32,
@@ -525,7 +3613,11 @@ fn draw_filled_square(
         (game::Mark::O, false) => egui::Color32::BLUE,
         (game::Mark::O, true) => egui::Color32::from_rgb(135, 206, 250), // light blue
     };
-    let stroke_width = if highlight { 4.0 } else { 2.0 };
+    let stroke_width = if highlight {
+        style.mark_thickness * 2.0
+    } else {
+        style.mark_thickness
+    };
 
     match square {
         game::Mark::X => {
@@ -555,23 +3647,28 @@ fn draw_filled_square(
     }
 }
 
-fn draw_grid_lines(ui: &mut egui::Ui, gh: GridHelper, highlight: bool) {
+fn draw_grid_lines(
+    ui: &mut egui::Ui,
+    style: &settings::BoardStyle,
+    gh: GridHelper,
+    highlight: bool,
+) {
     let painter = ui.painter();
 
     // grid config
     let rows = 3;
     let cols = 3;
-    let stroke = egui::Stroke::new(1.0, egui::Color32::LIGHT_GRAY);
+    let stroke = egui::Stroke::new(style.grid_thickness, style.grid_color);
 
     let cell_w = gh.rect.width() / cols as f32;
     let cell_h = gh.rect.height() / rows as f32;
 
     if highlight {
         painter.rect(
-            gh.rect.shrink(-4.0),
+            gh.rect.shrink(-style.highlight_thickness),
             3.0,
             egui::Color32::TRANSPARENT,
-            egui::Stroke::new(4.0, egui::Color32::GREEN),
+            egui::Stroke::new(style.highlight_thickness, style.highlight_color),
             egui::StrokeKind::Outside,
         );
     }
@@ -600,3 +3697,573 @@ fn draw_grid_lines(ui: &mut egui::Ui, gh: GridHelper, highlight: bool) {
         );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+    use game::{Mark, OuterBoard};
+
+    // the human's move the way a click plays it, with the computer asked for its answer
+    fn play_human_move(app: &mut App, r#move: game::Move) {
+        let board = app.board();
+        let mut new_state = *app.state();
+        new_state.board = board.try_move(r#move).unwrap();
+        new_state.last_player_move = Some(r#move);
+        new_state.to_move = !r#move.player;
+        app.states.push(new_state);
+        app.log_move(board, r#move, None);
+        app.request_search(app.computer());
+    }
+
+    fn answer_search(app: &mut App) {
+        let SearchResponse::Move(best, stats) = app.resp_rx.recv().unwrap() else {
+            panic!("the computer's search didn't answer with a move");
+        };
+        app.apply_engine_move(best, stats);
+    }
+
+    // scrubbing back through a game has to move every accessor to the same state, and a move
+    // made from an earlier state branches the game there
+    #[test]
+    fn viewing_a_state_shows_it() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut app = App::new(Default::default());
+        let mut to_move = Mark::X;
+        while !app.states.last().unwrap().board.is_over() {
+            let mut state = *app.states.last().unwrap();
+            let moves = state.board.possible_moves(to_move);
+            let r#move = moves[rng.random_range(0..moves.len())];
+            state.board = state.board.try_move(r#move).unwrap();
+            state.last_player_move = Some(r#move);
+            state.eval = app.states.len() as i32;
+            state.to_move = !to_move;
+            state.settle_end();
+            app.states.push(state);
+            to_move = !to_move;
+        }
+
+        let latest = app.states.len() - 1;
+        for index in 0..=latest {
+            app.viewing = (index != latest).then_some(index);
+            let state = app.states[index];
+            assert_eq!(app.board(), state.board, "state {index}");
+            assert_eq!(app.eval(), state.eval, "state {index}");
+            assert_eq!(
+                app.last_player_move(),
+                state.last_player_move,
+                "state {index}"
+            );
+            assert_eq!(app.to_move(), state.to_move, "state {index}");
+            let over = index == latest;
+            assert_eq!(app.game_over(), over, "state {index}");
+            assert_eq!(
+                app.overall_winner().is_some(),
+                over && state.board.overall_winner.is_some(),
+                "state {index}"
+            );
+            assert!(
+                app.viewing.is_none() || !app.human_can_move(),
+                "state {index}"
+            );
+        }
+        // the last state says why the game ended, and keeps saying it once saved and loaded
+        let expected = match app.states[latest].board.overall_winner {
+            Some(line_owner) => GameEnd::Line(line_owner),
+            None => GameEnd::Draw(DrawReason::NoLinesLeft),
+        };
+        assert_eq!(app.states[latest].end, Some(expected));
+        let loaded = decode_save(&encode_save(&app.states)).unwrap();
+        assert_eq!(loaded[latest].end, Some(expected));
+
+        app.viewing = Some(latest + 10);
+        assert_eq!(
+            app.eval(),
+            app.states[latest].eval,
+            "a stale index falls back"
+        );
+
+        // moving from an earlier position drops everything after it
+        let branch = (0..latest)
+            .find(|&index| app.states[index].to_move == game::HUMAN_MARK)
+            .expect("the human had the move");
+        app.viewing = Some(branch);
+        assert!(app.human_can_move_here());
+        app.branch_from_displayed();
+        assert_eq!(app.states.len(), branch + 1);
+        assert!(app.viewing.is_none() && app.human_can_move());
+    }
+
+    // a game saved before draws were tracked loads with every result worked out again, and one
+    // saved today loads unchanged
+    #[test]
+    fn legacy_saves_load() {
+        use game::{COMPUTER_MARK, HUMAN_MARK};
+        let mut rng = StdRng::seed_from_u64(4);
+        for _ in 0..20 {
+            let mut legacy = vec![];
+            let mut board = OuterBoard::default();
+            let mut to_move = HUMAN_MARK;
+            while !board.is_over() {
+                let moves = board.possible_moves(to_move);
+                let r#move = moves[rng.random_range(0..moves.len())];
+                board = board.try_move(r#move).unwrap();
+                // the old format had one state per human move, with the answer in it
+                if to_move == HUMAN_MARK {
+                    legacy.push(LegacyGameState {
+                        board: LegacyBoard {
+                            boards: board.boards,
+                            overall_winner: board.overall_winner,
+                            active_square: board.active_square,
+                        },
+                        last_player_move: Some(r#move),
+                        last_computer_move: None,
+                        eval: 0,
+                    });
+                } else {
+                    let state = legacy.last_mut().unwrap();
+                    state.board.boards = board.boards;
+                    state.board.overall_winner = board.overall_winner;
+                    state.board.active_square = board.active_square;
+                    state.last_computer_move = Some(r#move);
+                }
+                to_move = !to_move;
+            }
+
+            let bytes = bincode::encode_to_vec(&legacy, bincode::config::standard()).unwrap();
+            let states = decode_save(&bytes).unwrap();
+            let last = states.last().unwrap();
+            assert_eq!(last.board, board);
+            assert!(last.end.is_some());
+            let expected = if last.last_computer_move.is_some() {
+                HUMAN_MARK
+            } else {
+                COMPUTER_MARK
+            };
+            assert_eq!(last.to_move, expected);
+
+            let again = decode_save(&encode_save(&states)).unwrap();
+            assert_eq!(again.len(), states.len());
+            assert!(again.iter().zip(&states).all(|(a, b)| a.board == b.board));
+        }
+    }
+
+    // saves carry a header saying which version wrote them, and anything unreadable says why
+    #[test]
+    fn saves_tell_their_version() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let states: Vec<_> = (0..4)
+            .map(|_| {
+                GameState::root(
+                    OuterBoard::random(&mut rng, 0.4),
+                    Default::default(),
+                    Mark::O,
+                )
+            })
+            .collect();
+        let same = |loaded: &[GameState], human| {
+            loaded.len() == states.len()
+                && loaded
+                    .iter()
+                    .zip(&states)
+                    .all(|(a, b)| a.board == b.board && a.human == human)
+        };
+
+        let bytes = encode_save(&states);
+        assert!(bytes.starts_with(SAVE_MAGIC));
+        assert!(same(&decode_save(&bytes).unwrap(), Mark::O));
+        // versions 0 and 1 had no choice of mark, the human was always X
+        let old: Vec<_> = states
+            .iter()
+            .map(|state| GameStateV1 {
+                board: state.board,
+                last_player_move: state.last_player_move,
+                last_computer_move: state.last_computer_move,
+                eval: state.eval,
+                end: state.end,
+                to_move: state.to_move,
+                variant: state.variant,
+            })
+            .collect();
+        let headerless = bincode::encode_to_vec(&old, bincode::config::standard()).unwrap();
+        let version_1 = [SAVE_MAGIC.as_slice(), &1u32.to_le_bytes(), &headerless].concat();
+        for bytes in [&headerless, &version_1] {
+            assert!(same(&decode_save(bytes).unwrap(), Mark::X));
+        }
+
+        // versions 2 and 3 kept no clocks
+        let unclocked: Vec<_> = states
+            .iter()
+            .map(|state| GameStateV3 {
+                board: state.board,
+                last_player_move: state.last_player_move,
+                last_computer_move: state.last_computer_move,
+                eval: state.eval,
+                end: state.end,
+                to_move: state.to_move,
+                variant: state.variant,
+                human: state.human,
+            })
+            .collect();
+        let body = bincode::encode_to_vec(&unclocked, bincode::config::standard()).unwrap();
+        let version_3 = [SAVE_MAGIC.as_slice(), &3u32.to_le_bytes(), &body].concat();
+        assert!(same(&decode_save(&version_3).unwrap(), Mark::O));
+
+        let mut newer = bytes.clone();
+        newer[4..8].copy_from_slice(&(SAVE_VERSION + 1).to_le_bytes());
+        match decode_save(&newer) {
+            Err(SaveError::Unsupported { version }) if version == SAVE_VERSION + 1 => {}
+            other => panic!("a save from a newer version gave {:?}", other.err()),
+        }
+        for broken in [
+            &bytes[..bytes.len() / 2],
+            &bytes[..6],
+            &[bytes.clone(), vec![0]].concat(),
+        ] {
+            match decode_save(broken) {
+                Err(SaveError::Corrupt(_)) => {}
+                other => panic!("a {}-byte broken save gave {:?}", broken.len(), other.err()),
+            }
+        }
+    }
+
+    // self-play runs to the end, every state one legal human move (and its answer) past the last
+    #[test]
+    fn selfplay_plays_to_the_end() {
+        let to_depth = |max_depth| game::searcher::SearchConfig {
+            max_depth,
+            ..Default::default()
+        };
+        let states = run_selfplay(to_depth(2), to_depth(1));
+        let last = states.last().unwrap();
+        assert!(last.end.is_some() && last.board.is_over());
+        for pair in states.windows(2) {
+            let (before, after) = (&pair[0], &pair[1]);
+            let human = after.last_player_move.expect("a human move in every state");
+            let mut board = before.board.try_move(human).unwrap();
+            let mut mover = human.player;
+            if board != after.board
+                && let Some(computer) = after.last_computer_move
+            {
+                board = board.try_move(computer).unwrap();
+                mover = computer.player;
+            }
+            assert_eq!(board, after.board);
+            assert_eq!(after.to_move, !mover);
+        }
+    }
+
+    // with the human on O the computer opens as X, in the root state, and then it's the
+    // human's turn
+    #[test]
+    fn the_computer_opens_against_o() {
+        let mut app = App::new(settings::AppSettings {
+            human_mark: Mark::O,
+            ..Default::default()
+        });
+        assert!(app.thinking && !app.human_can_move());
+        answer_search(&mut app);
+        assert_eq!(app.states.len(), 1);
+        assert_eq!(
+            app.last_computer_move().map(|r#move| r#move.player),
+            Some(Mark::X)
+        );
+        assert_eq!(app.to_move(), Mark::O);
+        assert!(app.human_can_move());
+        let shown =
+            settings::EvalPerspective::Human.apply(100, app.computer(), Mark::O, app.human());
+        assert_eq!(shown, -100);
+    }
+
+    // a hint is a legal move for the human, and asking for one doesn't play it
+    #[test]
+    fn a_hint_isnt_played() {
+        let mut app = App::new(Default::default());
+        app.send_request(SearchKind::Hint, app.human());
+        assert!(app.thinking);
+        let SearchResponse::Hint(Some(hint)) = app.resp_rx.recv().unwrap() else {
+            panic!("the hint search didn't answer with a move");
+        };
+        assert_eq!(hint.player, app.human());
+        assert!(app.board().make_move(hint).is_some());
+        assert_eq!(app.states.len(), 1);
+        assert!(app.game_moves.is_empty());
+    }
+
+    // undo and redo round trip a move and its answer, a fresh move drops what could be redone,
+    // and redoing a move the computer hadn't answered yet asks it again
+    #[test]
+    fn undo_and_redo_round_trip() {
+        let mut app = App::new(Default::default());
+        let first = app.board().possible_moves(app.human())[0];
+        play_human_move(&mut app, first);
+        answer_search(&mut app);
+        let played = app.board();
+        app.undo();
+        assert_eq!(app.states.len(), 1);
+        assert!(app.game_moves.is_empty());
+        assert_eq!(app.redo_stack.len(), 1);
+        app.redo();
+        assert_eq!(app.board(), played);
+        assert_eq!(app.game_moves.len(), 2);
+        assert!(app.redo_stack.is_empty());
+        assert!(
+            !app.thinking,
+            "redoing an answered move asked the computer again"
+        );
+
+        app.undo();
+        let second = app.board().possible_moves(app.human())[1];
+        play_human_move(&mut app, second);
+        assert!(
+            app.redo_stack.is_empty(),
+            "a fresh move kept the undone one"
+        );
+        app.undo();
+        assert!(!app.thinking, "undo left the computer searching");
+        app.redo();
+        assert!(app.thinking && app.game_moves.len() == 1);
+        answer_search(&mut app);
+        assert_eq!(app.game_moves.len(), 2);
+        assert!(app.redo_stack.is_empty());
+    }
+
+    // a clock gets its increment after each move, and the side whose clock runs out loses
+    #[test]
+    fn the_clock_ticks_and_flags() {
+        use std::time::Duration;
+        let mut app = App::new(settings::AppSettings {
+            clock_enabled: true,
+            clock: settings::ClockConfig {
+                initial: Duration::from_secs(60),
+                increment: Duration::from_secs(3),
+            },
+            ..Default::default()
+        });
+        let ctx = egui::Context::default();
+        let frame_at = |time: f64| {
+            ctx.begin_pass(egui::RawInput {
+                time: Some(time),
+                ..Default::default()
+            });
+        };
+        frame_at(0.0);
+        let _ = ctx.end_pass();
+        // the human is X and to move, so only their clock runs
+        frame_at(10.5);
+        app.tick_clock(&ctx);
+        let _ = ctx.end_pass();
+        assert_eq!(format_clock(app.remaining_x), "00:50");
+        assert_eq!(app.remaining_o, Duration::from_secs(60));
+        let board = app.board();
+        let r#move = board.possible_moves(app.human())[0];
+        app.states.last_mut().unwrap().board = board.try_move(r#move).unwrap();
+        app.log_move(board, r#move, None);
+        assert_eq!(app.remaining_x, Duration::from_millis(52_500));
+
+        app.states.last_mut().unwrap().to_move = app.human();
+        frame_at(100.0);
+        app.tick_clock(&ctx);
+        let _ = ctx.end_pass();
+        assert_eq!(app.end(), Some(GameEnd::Timeout(app.human())));
+        assert_eq!(app.overall_winner(), Some(app.computer()));
+    }
+
+    // whose clock runs depends on the turn alone: not on hotseat, and not on a hint being
+    // searched for the human
+    #[test]
+    fn the_clock_follows_the_turn() {
+        use std::time::Duration;
+        let clocked = settings::AppSettings {
+            clock_enabled: true,
+            clock: settings::ClockConfig {
+                initial: Duration::from_secs(60),
+                increment: Duration::ZERO,
+            },
+            ..Default::default()
+        };
+        let ctx = egui::Context::default();
+        let frame_at = |app: &mut App, time: f64| {
+            ctx.begin_pass(egui::RawInput {
+                time: Some(time),
+                ..Default::default()
+            });
+            app.tick_clock(&ctx);
+            let _ = ctx.end_pass();
+        };
+
+        let mut app = App::new(clocked.clone());
+        frame_at(&mut app, 0.0);
+        app.send_request(SearchKind::Hint, app.human());
+        frame_at(&mut app, 10.0);
+        assert_eq!(
+            app.remaining_x,
+            Duration::from_secs(50),
+            "a hint froze the clock"
+        );
+        app.cancel_search();
+
+        let mut app = App::with_hotseat(clocked, true);
+        frame_at(&mut app, 0.0);
+        app.states.last_mut().unwrap().to_move = app.computer();
+        frame_at(&mut app, 10.0);
+        assert_eq!(
+            app.remaining_o,
+            Duration::from_secs(50),
+            "O's clock stood in hotseat"
+        );
+        assert_eq!(app.remaining_x, Duration::from_secs(60));
+    }
+
+    // undo puts the clocks back to where they were, and redo to where the move left them, so
+    // no increment gets added twice
+    #[test]
+    fn undo_and_redo_put_the_clocks_back() {
+        use std::time::Duration;
+        let mut app = App::with_hotseat(
+            settings::AppSettings {
+                clock_enabled: true,
+                clock: settings::ClockConfig {
+                    initial: Duration::from_secs(60),
+                    increment: Duration::from_secs(3),
+                },
+                ..Default::default()
+            },
+            true,
+        );
+        let before = (app.remaining_x, app.remaining_o);
+        let first = app.board().possible_moves(app.human())[0];
+        let board = app.board();
+        push_move(&mut app.states, first, board.try_move(first).unwrap(), 0);
+        app.log_move(board, first, None);
+        app.remaining_o -= Duration::from_secs(5);
+        let answer = app.board().possible_moves(app.computer())[0];
+        let board = app.board();
+        push_move(&mut app.states, answer, board.try_move(answer).unwrap(), 0);
+        app.log_move(board, answer, None);
+        let after = (app.remaining_x, app.remaining_o);
+        assert_eq!(after, (Duration::from_secs(63), Duration::from_secs(58)));
+
+        app.undo();
+        assert_eq!((app.remaining_x, app.remaining_o), before);
+        app.redo();
+        assert_eq!((app.remaining_x, app.remaining_o), after);
+        app.undo();
+        app.redo();
+        assert_eq!((app.remaining_x, app.remaining_o), after);
+    }
+
+    // the eval bar's curve: even at zero, three to one at `EVAL_SCALE`, rising all the way to
+    // the terminal scores at its ends
+    #[test]
+    fn win_probability_rises_with_the_eval() {
+        let p = win_probability;
+        let scale = EVAL_SCALE as i32;
+        assert_eq!(p(0), 0.5);
+        assert!((p(scale) - 0.75).abs() <= 1e-4);
+        assert_eq!(p(i32::MAX), 1.0);
+        assert_eq!(p(i32::MIN), 0.0);
+        let evals = [
+            i32::MIN,
+            -1_000_000,
+            -scale,
+            -1,
+            0,
+            1,
+            scale,
+            1_000_000,
+            i32::MAX,
+        ];
+        assert!(evals.windows(2).all(|pair| p(pair[0]) <= p(pair[1])));
+    }
+
+    // a typed seed is the one "Partita a caso" uses
+    #[test]
+    fn a_typed_seed_is_used() {
+        let mut app = App::new(Default::default());
+        app.seed_input = " 1234 ".to_string();
+        assert_eq!(
+            app.random_start(),
+            reproducer::Start::Random {
+                seed: 1234,
+                fill: app.settings.random_fill_percentage,
+            }
+        );
+    }
+
+    // a lone legal move gets played without ever thinking
+    #[test]
+    fn an_only_move_is_played_at_once() {
+        let mut app = App::new(Default::default());
+        let mut board = OuterBoard::default();
+        board.boards[0][0].squares = [
+            [Mark::X, Mark::O, Mark::X],
+            [Mark::X, Mark::O, Mark::O],
+            [Mark::O, Mark::X, Mark::X],
+        ]
+        .map(|row| row.map(Some));
+        board.boards[0][0].squares[1][1] = None;
+        board.recompute_winners();
+        board.active_square = Some((0, 0));
+        let player = app.computer();
+        let only = board.possible_moves(player)[0];
+        app.states[0].board = board;
+        app.states[0].to_move = player;
+        app.start_computer_if_its_turn();
+        assert!(!app.thinking);
+        assert_eq!(app.game_moves.len(), 1);
+        assert_eq!(app.board(), board.make_move(only).unwrap());
+    }
+
+    // resetting in hotseat starts an empty board with nobody thinking, whichever mark the
+    // human had
+    #[test]
+    fn reset_in_hotseat_leaves_the_board_to_the_players() {
+        let mut app = App::new(settings::AppSettings {
+            human_mark: Mark::O,
+            ..Default::default()
+        });
+        answer_search(&mut app);
+        app.hotseat = true;
+        app.restart();
+        assert!(app.hotseat);
+        assert!(
+            !app.thinking,
+            "the computer started thinking after a reset in hotseat"
+        );
+        assert_eq!(app.states.len(), 1);
+        assert_eq!(app.board(), OuterBoard::default());
+        assert!(app.game_moves.is_empty());
+    }
+
+    // a pasted list of moves rebuilds the game, and a bad one leaves the current game alone
+    #[test]
+    fn a_pasted_transcript_rebuilds_the_game() {
+        let transcript = "X11-22, O22-11 X11-11";
+        let reproducer =
+            reproducer::parse_transcript(transcript, game::Variant::Standard, Default::default())
+                .unwrap();
+        let mut app = App::new(Default::default());
+        app.load_reproducer(&reproducer);
+        assert_eq!(app.game_moves.len(), 3);
+        assert_eq!(app.states.len(), 3);
+        assert_eq!(app.message, None);
+
+        let board = app.board();
+        let illegal = "X11-22 O11-11 X22-22";
+        let reproducer =
+            reproducer::parse_transcript(illegal, game::Variant::Standard, Default::default())
+                .unwrap();
+        app.load_reproducer(&reproducer);
+        assert_eq!(app.board(), board);
+        assert_eq!(app.game_moves.len(), 3);
+        assert!(
+            app.message
+                .as_ref()
+                .is_some_and(|message| message.contains("mossa 2")),
+            "{:?}",
+            app.message
+        );
+    }
+}
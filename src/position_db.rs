@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use tracing::{error, info};
+
+use crate::game::{Mark, Move, OuterBoard};
+
+const FILE_NAME: &str = "positions.bin";
+// past this many positions the least visited ones get dropped
+const MAX_POSITIONS: usize = 50_000;
+
+#[derive(Clone, Copy, Debug, Default, bincode::Encode, bincode::Decode)]
+pub struct Results {
+    pub x_wins: u32,
+    pub o_wins: u32,
+    pub draws: u32,
+}
+
+impl Results {
+    fn add(&mut self, winner: Option<Mark>) {
+        match winner {
+            Some(Mark::X) => self.x_wins += 1,
+            Some(Mark::O) => self.o_wins += 1,
+            None => self.draws += 1,
+        }
+    }
+}
+
+// moves are stored in the frame of the canonical board, see `OuterBoard::canonical`
+#[derive(Clone, Debug, Default, bincode::Encode, bincode::Decode)]
+pub struct PositionEntry {
+    pub seen: u32,
+    pub results: Results,
+    pub moves: Vec<(Move, Results)>,
+}
+
+#[derive(Default, bincode::Encode, bincode::Decode)]
+pub struct PositionDb {
+    positions: HashMap<u64, PositionEntry>,
+}
+
+impl PositionDb {
+    fn path() -> Option<PathBuf> {
+        eframe::storage_dir("Supertris").map(|dir| dir.join(FILE_NAME))
+    }
+
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        let Ok(mut file) = std::fs::File::open(&path) else {
+            return Self::default();
+        };
+        bincode::decode_from_std_read(&mut file, bincode::config::standard()).unwrap_or_else(|e| {
+            error!(error = ?e, "position_db_load_error");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let Some(path) = Self::path() else {
+            error!("position_db_no_storage_dir");
+            return;
+        };
+        match self.write(&path) {
+            Ok(()) => info!(
+                path = %path.display(),
+                positions = self.positions.len(),
+                "position_db_saved"
+            ),
+            Err(e) => error!(error = ?e, "position_db_save_error"),
+        }
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = std::fs::File::create(path)?;
+        bincode::encode_into_std_write(self, &mut file, bincode::config::standard())?;
+        Ok(())
+    }
+
+    pub fn record_game(&mut self, moves: &[(OuterBoard, Move)], winner: Option<Mark>) {
+        for &(board, r#move) in moves {
+            let (canonical, transform) = board.canonical();
+            let r#move = r#move.transformed::<3>(transform);
+            let entry = self
+                .positions
+                .entry(canonical.canonical_hash())
+                .or_default();
+            entry.seen += 1;
+            entry.results.add(winner);
+            match entry.moves.iter_mut().find(|(m, _)| *m == r#move) {
+                Some((_, results)) => results.add(winner),
+                None => {
+                    let mut results = Results::default();
+                    results.add(winner);
+                    entry.moves.push((r#move, results));
+                }
+            }
+        }
+
+        if self.positions.len() > MAX_POSITIONS {
+            self.evict();
+        }
+    }
+
+    fn evict(&mut self) {
+        let mut seen: Vec<u32> = self.positions.values().map(|entry| entry.seen).collect();
+        let keep = MAX_POSITIONS * 3 / 4;
+        let (_, &mut cutoff, _) = seen.select_nth_unstable_by(keep, |a, b| b.cmp(a));
+        self.positions.retain(|_, entry| entry.seen > cutoff);
+        info!(positions = self.positions.len(), "position_db_evicted");
+    }
+
+    // the entry for `board`, with its moves mapped back into the board's own orientation
+    pub fn lookup(&self, board: &OuterBoard) -> Option<PositionEntry> {
+        let (_, transform) = board.canonical();
+        let mut entry = self.positions.get(&board.canonical_hash())?.clone();
+        for (r#move, _) in &mut entry.moves {
+            *r#move = r#move.untransformed::<3>(transform);
+        }
+        entry.moves.sort_by_key(|(_, results)| {
+            std::cmp::Reverse(results.x_wins + results.o_wins + results.draws)
+        });
+        Some(entry)
+    }
+}
@@ -0,0 +1,64 @@
+use color_eyre::eyre::Result;
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::game::{OuterBoard, searcher};
+
+// openings, middlegames and endgames, in that order
+const PLIES: [usize; 3] = [4, 16, 28];
+const SEEDS: std::ops::Range<u64> = 0..4;
+// seconds, around the default `MAX_SEARCH_TIME`
+const TIME_LIMITS: [f64; 5] = [0.05, 0.1, 0.25, 0.5, 1.0];
+
+// `supertris profile`: how deep iterative deepening gets within each time limit, on one
+// thread so that runs are comparable
+pub fn run() -> Result<()> {
+    let limits = searcher::resource_limits();
+    searcher::set_resource_limits(searcher::ResourceLimits {
+        threads: Some(1),
+        ..limits
+    });
+
+    // rows are printed whole, so the search's own logging can only go between them
+    let mut header = format!("{:<12}", "posizione");
+    for time_limit in TIME_LIMITS {
+        header += &format!("{:>22}", format!("{} ms", (time_limit * 1000.0) as u32));
+    }
+    let mut rows = vec![header];
+    for plies in PLIES {
+        for seed in SEEDS {
+            let (board, to_move) =
+                OuterBoard::random_playout(&mut StdRng::seed_from_u64(seed), plies);
+            if board.is_over() {
+                continue;
+            }
+            let mut row = format!("{:<12}", format!("{plies}/{seed}"));
+            for time_limit in TIME_LIMITS {
+                searcher::clear_transposition_table();
+                let (_, result) = searcher::Searcher::search_multipv(
+                    &board,
+                    to_move,
+                    searcher::SearchConfig {
+                        max_time: std::time::Duration::from_secs_f64(time_limit),
+                        ..Default::default()
+                    },
+                    Default::default(),
+                    1,
+                );
+                row += &format!(
+                    "{:>22}",
+                    format!(
+                        "d{} {}n {}ms",
+                        result.depth,
+                        result.nodes,
+                        result.elapsed.as_millis()
+                    )
+                );
+            }
+            rows.push(row);
+        }
+    }
+    println!("{}", rows.join("\n"));
+
+    searcher::set_resource_limits(limits);
+    Ok(())
+}
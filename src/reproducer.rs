@@ -0,0 +1,255 @@
+use color_eyre::eyre::{Result, bail, eyre};
+
+use crate::game::{Mark, Move, OuterBoard, Ruleset, Variant, searcher};
+
+const HEADER: &str = "supertris riproduttore";
+// what `supertris reproduce` searches to; fixed so that every run visits the same tree
+const SEARCH_DEPTH: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Start {
+    Empty,
+    // `OuterBoard::random_seeded`
+    Random { seed: u64, fill: f64 },
+}
+
+impl Start {
+    pub fn board(self) -> OuterBoard {
+        match self {
+            Start::Empty => OuterBoard::default(),
+            Start::Random { seed, fill } => OuterBoard::random_seeded(fill, seed),
+        }
+    }
+}
+
+// everything needed to rebuild a game exactly: where it started and every move since
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reproducer {
+    pub start: Start,
+    pub variant: Variant,
+    pub ruleset: Ruleset,
+    pub first: Mark,
+    pub moves: Vec<Move>,
+}
+
+impl Reproducer {
+    pub fn start_board(&self) -> OuterBoard {
+        OuterBoard {
+            ruleset: self.ruleset,
+            ..self.start.board()
+        }
+    }
+
+    // the board before and after every move, or the first move that can't be played
+    pub fn replay(&self) -> Result<Vec<OuterBoard>> {
+        let mut to_move = self.first;
+        for (i, &r#move) in self.moves.iter().enumerate() {
+            if r#move.player != to_move {
+                bail!("mossa {} ({move}): tocca a {to_move}", i + 1);
+            }
+            to_move = !to_move;
+        }
+        Ok(self.start_board().replay(&self.moves)?)
+    }
+
+    pub fn to_move(&self) -> Mark {
+        self.moves
+            .last()
+            .map_or(self.first, |r#move| !r#move.player)
+    }
+}
+
+impl std::fmt::Display for Reproducer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        match self.start {
+            Start::Empty => writeln!(f, "partenza: vuota")?,
+            Start::Random { seed, fill } => writeln!(f, "partenza: casuale {seed} {fill}")?,
+        }
+        writeln!(f, "variante: {:?}", self.variant)?;
+        writeln!(f, "regole: {:?}", self.ruleset)?;
+        writeln!(f, "primo: {}", self.first)?;
+        write!(f, "mosse:")?;
+        for r#move in &self.moves {
+            write!(f, " {move}")?;
+        }
+        writeln!(f)
+    }
+}
+
+impl std::str::FromStr for Reproducer {
+    type Err = color_eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut lines = s.lines().map(str::trim).filter(|line| !line.is_empty());
+        if lines.next() != Some(HEADER) {
+            bail!("manca l'intestazione \"{HEADER}\"");
+        }
+
+        let mut start = None;
+        let mut variant = None;
+        let mut ruleset = None;
+        let mut first = None;
+        let mut moves = None;
+        for line in lines {
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| eyre!("riga non valida: {line}"))?;
+            let value = value.trim();
+            match key.trim() {
+                "partenza" => start = Some(parse_start(value)?),
+                "variante" => {
+                    variant = Some(match value {
+                        "Standard" => Variant::Standard,
+                        "Misere" => Variant::Misere,
+                        _ => bail!("variante sconosciuta: {value}"),
+                    })
+                }
+                "regole" => {
+                    ruleset = Some(match value {
+                        "FreeChoice" => Ruleset::FreeChoice,
+                        "WonBoardsStayOpen" => Ruleset::WonBoardsStayOpen,
+                        _ => bail!("regole sconosciute: {value}"),
+                    })
+                }
+                "primo" => {
+                    first = Some(match value {
+                        "X" => Mark::X,
+                        "O" => Mark::O,
+                        _ => bail!("giocatore sconosciuto: {value}"),
+                    })
+                }
+                "mosse" => {
+                    moves = Some(
+                        value
+                            .split_whitespace()
+                            .map(|token| token.parse().map_err(|e| eyre!("mossa \"{token}\": {e}")))
+                            .collect::<Result<_>>()?,
+                    )
+                }
+                key => bail!("campo sconosciuto: {key}"),
+            }
+        }
+
+        Ok(Self {
+            start: start.ok_or_else(|| eyre!("manca \"partenza\""))?,
+            variant: variant.unwrap_or_default(),
+            ruleset: ruleset.unwrap_or_default(),
+            first: first.unwrap_or(Mark::X),
+            moves: moves.unwrap_or_default(),
+        })
+    }
+}
+
+// what someone pastes: a whole reproducer, or just the moves of a game from the empty board
+pub fn parse_transcript(text: &str, variant: Variant, ruleset: Ruleset) -> Result<Reproducer> {
+    if text.trim_start().starts_with(HEADER) {
+        return text.parse();
+    }
+    let moves: Vec<Move> = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty())
+        .map(|token| token.parse().map_err(|e| eyre!("mossa \"{token}\": {e}")))
+        .collect::<Result<_>>()?;
+    let Some(first) = moves.first() else {
+        bail!("nessuna mossa da ricostruire");
+    };
+    Ok(Reproducer {
+        start: Start::Empty,
+        variant,
+        ruleset,
+        first: first.player,
+        moves,
+    })
+}
+
+fn parse_start(value: &str) -> Result<Start> {
+    let mut words = value.split_whitespace();
+    match words.next() {
+        Some("vuota") => Ok(Start::Empty),
+        Some("casuale") => {
+            let (Some(seed), Some(fill)) = (words.next(), words.next()) else {
+                bail!("la partenza casuale vuole un seme e una percentuale");
+            };
+            Ok(Start::Random {
+                seed: seed.parse()?,
+                fill: fill.parse()?,
+            })
+        }
+        _ => bail!("partenza sconosciuta: {value}"),
+    }
+}
+
+// `supertris reproduce <file>`: replays the game and prints what the deterministic search plays
+pub fn run(path: Option<String>) -> Result<()> {
+    let path = path.ok_or_else(|| eyre!("uso: supertris reproduce <file>"))?;
+    let reproducer: Reproducer = std::fs::read_to_string(path)?.parse()?;
+    let board = *reproducer.replay()?.last().unwrap();
+    println!("{board}");
+
+    let to_move = reproducer.to_move();
+    if board.is_over() {
+        println!("partita finita, vincitore: {:?}", board.overall_winner);
+        return Ok(());
+    }
+    searcher::clear_transposition_table();
+    let (best, stats) = searcher::Searcher::search_deterministic(
+        &board,
+        to_move,
+        SEARCH_DEPTH,
+        searcher::SearchOptions {
+            variant: reproducer.variant,
+            ..Default::default()
+        },
+    );
+    match best {
+        Some((r#move, eval)) => println!(
+            "{to_move} gioca {move} (valutazione {eval}, {} nodi a profondità {SEARCH_DEPTH})",
+            stats.nodes
+        ),
+        None => println!("{to_move} non ha mosse"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    use super::*;
+
+    // a reproducer has to survive being copied as text and rebuild exactly the game it came
+    // from
+    #[test]
+    fn a_reproducer_round_trips_through_text() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for seed in 0..20 {
+            let start = if seed % 2 == 0 {
+                Start::Empty
+            } else {
+                Start::Random { seed, fill: 0.3 }
+            };
+            assert_eq!(start.board(), start.board(), "seed {seed}");
+            let mut reproducer = Reproducer {
+                start,
+                variant: Variant::Standard,
+                ruleset: Default::default(),
+                first: Mark::X,
+                moves: vec![],
+            };
+            let mut board = reproducer.start_board();
+            let mut to_move = reproducer.first;
+            while !board.is_over() && reproducer.moves.len() < 30 {
+                let moves = board.possible_moves(to_move);
+                let r#move = moves[rng.random_range(0..moves.len())];
+                board = board.try_move(r#move).unwrap();
+                reproducer.moves.push(r#move);
+                to_move = !to_move;
+            }
+
+            let parsed: Reproducer = reproducer.to_string().parse().unwrap();
+            assert_eq!(parsed, reproducer);
+            assert_eq!(parsed.replay().unwrap().last(), Some(&board));
+        }
+    }
+}
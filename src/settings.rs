@@ -0,0 +1,371 @@
+use std::time::Duration;
+
+use eframe::egui;
+
+use crate::game::{
+    self, Mark, Ruleset, Variant,
+    searcher::{Difficulty, Personality},
+};
+
+pub const STORAGE_KEY: &str = "settings";
+
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct BoardStyle {
+    pub grid_thickness: f32,
+    pub grid_color: egui::Color32,
+    pub highlight_thickness: f32,
+    pub highlight_color: egui::Color32,
+    pub mark_thickness: f32,
+}
+
+impl Default for BoardStyle {
+    fn default() -> Self {
+        Self {
+            grid_thickness: 1.0,
+            grid_color: egui::Color32::LIGHT_GRAY,
+            highlight_thickness: 4.0,
+            highlight_color: egui::Color32::GREEN,
+            mark_thickness: 2.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum Heatmap {
+    #[default]
+    Off,
+    CurrentGame,
+    GameLog,
+}
+
+// whose point of view the evals in the side panel are shown from; searches don't care
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Serialize, serde::Deserialize)]
+pub enum EvalPerspective {
+    X,
+    // the computer's side, which is what every eval was shown as before this was a setting
+    #[default]
+    O,
+    SideToMove,
+    Human,
+}
+
+impl EvalPerspective {
+    // `eval` is from `from`'s point of view, `to_move` is who plays next in that position and
+    // `human` is the mark the human plays this game with
+    pub fn apply(self, eval: i32, from: Mark, to_move: Mark, human: Mark) -> i32 {
+        let reference = match self {
+            EvalPerspective::X => Mark::X,
+            EvalPerspective::O => Mark::O,
+            EvalPerspective::SideToMove => to_move,
+            EvalPerspective::Human => human,
+        };
+        if reference == from {
+            eval
+        } else {
+            eval.saturating_neg()
+        }
+    }
+}
+
+// chess-style: every side starts with `initial` and gets `increment` back after each move
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ClockConfig {
+    pub initial: Duration,
+    pub increment: Duration,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(5 * 60),
+            increment: Duration::from_secs(3),
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub random_fill_percentage: f64,
+    // redraw random boards until the heuristic is within `balance_band` of zero
+    pub balanced_random: bool,
+    pub balance_band: i32,
+    pub keep_playing_for_me: bool,
+    // a side with a single legal move plays it without asking the engine
+    pub instant_moves: bool,
+    pub variant: Variant,
+    pub ruleset: Ruleset,
+    // which mark the human plays in new games; X always moves first
+    pub human_mark: Mark,
+    // the clocks new games get, if any
+    pub clock_enabled: bool,
+    pub clock: ClockConfig,
+    pub touch_mode: bool,
+    pub record_positions: bool,
+    pub fullscreen: bool,
+    pub developer_mode: bool,
+    pub analysis_arrows: bool,
+    // while the computer thinks, point out the subboard the human's move sent it to
+    pub show_computer_target: bool,
+    // tint open subboards someone can win with their next mark there
+    pub threat_tint: bool,
+    // shade every cell the human can play in on their turn
+    pub legal_move_hints: bool,
+    // warn when a move under the mouse hands the opponent a subboard or a double threat, and
+    // when the computer left no reply that doesn't
+    pub trap_warnings: bool,
+    pub eval_bar: bool,
+    pub game_log_enabled: bool,
+    pub game_log_path: String,
+    pub blunder_threshold: i32,
+    // `None` searches every move, as the full-strength engine does
+    pub max_moves_considered: Option<usize>,
+    // above zero the computer avoids draws it could have, below zero it looks for them
+    pub contempt: i32,
+    pub heatmap: Heatmap,
+    pub personality: Personality,
+    pub difficulty: Difficulty,
+    // how strong each side plays in "Autopartita"
+    pub autoplay_x: Difficulty,
+    pub autoplay_o: Difficulty,
+    pub tutorial_seen: bool,
+    // no winner reveal, spinner or panel slide, so the app only redraws on input
+    pub power_saver: bool,
+    pub eval_perspective: EvalPerspective,
+    // a weaker engine, plus a note whenever the human misses a much better move
+    pub coaching: bool,
+    pub style: BoardStyle,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            random_fill_percentage: 0.5,
+            balanced_random: false,
+            balance_band: 300,
+            keep_playing_for_me: false,
+            instant_moves: true,
+            variant: Variant::Standard,
+            ruleset: Ruleset::FreeChoice,
+            human_mark: game::HUMAN_MARK,
+            clock_enabled: false,
+            clock: ClockConfig::default(),
+            touch_mode: false,
+            record_positions: false,
+            fullscreen: false,
+            developer_mode: false,
+            analysis_arrows: true,
+            show_computer_target: false,
+            threat_tint: false,
+            legal_move_hints: true,
+            trap_warnings: false,
+            eval_bar: true,
+            game_log_enabled: false,
+            game_log_path: "supertris_games.jsonl".to_string(),
+            blunder_threshold: 300,
+            max_moves_considered: None,
+            contempt: 0,
+            heatmap: Heatmap::Off,
+            personality: Personality::Balanced,
+            difficulty: Difficulty::Medium,
+            autoplay_x: Difficulty::Medium,
+            autoplay_o: Difficulty::Medium,
+            tutorial_seen: false,
+            power_saver: false,
+            eval_perspective: EvalPerspective::O,
+            coaching: false,
+            style: BoardStyle::default(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn autoplay_difficulty(&self, mark: Mark) -> Difficulty {
+        match mark {
+            Mark::X => self.autoplay_x,
+            Mark::O => self.autoplay_o,
+        }
+    }
+
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        storage
+            .and_then(|storage| eframe::get_value(storage, STORAGE_KEY))
+            .unwrap_or_default()
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Impostazioni");
+        ui.separator();
+
+        ui.label("Partita");
+        ui.add(
+            egui::Slider::new(&mut self.random_fill_percentage, 0.0..=1.0)
+                .text("Percentuale di caselle riempite"),
+        );
+        ui.horizontal(|ui| {
+            ui.checkbox(
+                &mut self.balanced_random,
+                "Solo partenze equilibrate, entro",
+            );
+            ui.add_enabled(
+                self.balanced_random,
+                egui::Slider::new(&mut self.balance_band, 50..=2000).text("punti"),
+            );
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(
+                &mut self.clock_enabled,
+                "Orologio (dalla prossima partita):",
+            );
+            let mut minutes = self.clock.initial.as_secs() / 60;
+            let mut increment = self.clock.increment.as_secs();
+            ui.add_enabled(
+                self.clock_enabled,
+                egui::Slider::new(&mut minutes, 1..=60).text("minuti"),
+            );
+            ui.add_enabled(
+                self.clock_enabled,
+                egui::Slider::new(&mut increment, 0..=30).text("secondi a mossa"),
+            );
+            self.clock = ClockConfig {
+                initial: Duration::from_secs(minutes * 60),
+                increment: Duration::from_secs(increment),
+            };
+        });
+        ui.checkbox(&mut self.keep_playing_for_me, "Gioca per me fino alla fine");
+        ui.checkbox(
+            &mut self.instant_moves,
+            "Mossa immediata quando ce n'è una sola",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Variante (dalla prossima partita):");
+            ui.selectable_value(&mut self.variant, Variant::Standard, "Normale");
+            ui.selectable_value(&mut self.variant, Variant::Misere, "Misère");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Sottotabelle vinte (dalla prossima partita):");
+            ui.selectable_value(&mut self.ruleset, Ruleset::FreeChoice, "Chiuse");
+            ui.selectable_value(&mut self.ruleset, Ruleset::WonBoardsStayOpen, "Aperte");
+        });
+        ui.checkbox(
+            &mut self.record_positions,
+            "Registra le posizioni giocate nel database",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Stile della CPU:");
+            for personality in Personality::ALL {
+                ui.selectable_value(&mut self.personality, personality, personality.to_string());
+            }
+        });
+        for (mark, difficulty) in [("X", &mut self.autoplay_x), ("O", &mut self.autoplay_o)] {
+            ui.horizontal(|ui| {
+                ui.label(format!("Autopartita, {mark}:"));
+                for choice in Difficulty::ALL {
+                    ui.selectable_value(difficulty, choice, choice.to_string());
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            let mut limited = self.max_moves_considered.is_some();
+            ui.checkbox(&mut limited, "Motore più debole: considera solo");
+            let mut limit = self.max_moves_considered.unwrap_or(3);
+            ui.add_enabled(limited, egui::Slider::new(&mut limit, 1..=9).text("mosse"));
+            self.max_moves_considered = limited.then_some(limit);
+        });
+        ui.add(
+            egui::Slider::new(&mut self.contempt, -1000..=1000)
+                .text("Quanto la CPU evita la patta"),
+        );
+        ui.checkbox(
+            &mut self.coaching,
+            "Allenamento: CPU più debole che segnala le mosse sbagliate",
+        );
+        ui.add(
+            egui::Slider::new(&mut self.blunder_threshold, 50..=2000)
+                .text("Calo di valutazione da segnalare come errore"),
+        );
+        ui.checkbox(
+            &mut self.game_log_enabled,
+            "Scrivi le mosse in un file JSON lines",
+        );
+        ui.add_enabled_ui(self.game_log_enabled, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("File:");
+                ui.text_edit_singleline(&mut self.game_log_path);
+            });
+        });
+
+        ui.separator();
+        ui.label("Aspetto");
+        ui.checkbox(
+            &mut self.touch_mode,
+            "Modalità touch (tocca una sottotabella per ingrandirla)",
+        );
+        ui.checkbox(
+            &mut self.analysis_arrows,
+            "Frecce sulle mosse consigliate dall'analisi",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Valutazioni dal punto di vista di:");
+            let perspective = &mut self.eval_perspective;
+            ui.selectable_value(perspective, EvalPerspective::X, "X");
+            ui.selectable_value(perspective, EvalPerspective::O, "O");
+            ui.selectable_value(perspective, EvalPerspective::SideToMove, "Chi muove");
+            ui.selectable_value(perspective, EvalPerspective::Human, "Me");
+        });
+        ui.checkbox(
+            &mut self.show_computer_target,
+            "Evidenzia dove deve giocare il computer",
+        );
+        ui.checkbox(
+            &mut self.threat_tint,
+            "Colora le sottotabelle a un passo dal tris",
+        );
+        ui.checkbox(
+            &mut self.legal_move_hints,
+            "Evidenzia le caselle dove puoi giocare",
+        );
+        ui.checkbox(
+            &mut self.trap_warnings,
+            "Avvisa delle trappole dell'avversario",
+        );
+        ui.checkbox(
+            &mut self.eval_bar,
+            "Barra di valutazione accanto al tabellone",
+        );
+        ui.horizontal(|ui| {
+            ui.label("Mappa di calore delle mosse:");
+            ui.selectable_value(&mut self.heatmap, Heatmap::Off, "No");
+            ui.selectable_value(&mut self.heatmap, Heatmap::CurrentGame, "Questa partita");
+            ui.selectable_value(&mut self.heatmap, Heatmap::GameLog, "File delle mosse");
+        });
+        ui.checkbox(
+            &mut self.power_saver,
+            "Risparmio energetico (niente animazioni)",
+        );
+        let style = &mut self.style;
+        ui.add(egui::Slider::new(&mut style.grid_thickness, 0.5..=6.0).text("Spessore griglia"));
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgba(&mut style.grid_color);
+            ui.label("Colore griglia");
+        });
+        ui.add(
+            egui::Slider::new(&mut style.highlight_thickness, 1.0..=10.0)
+                .text("Spessore evidenziazione"),
+        );
+        ui.horizontal(|ui| {
+            ui.color_edit_button_srgba(&mut style.highlight_color);
+            ui.label("Colore evidenziazione");
+        });
+        ui.add(egui::Slider::new(&mut style.mark_thickness, 0.5..=8.0).text("Spessore segni"));
+
+        ui.separator();
+        ui.checkbox(&mut self.developer_mode, "Modalità sviluppatore");
+
+        ui.separator();
+        if ui.button("Ripristina predefinite").clicked() {
+            *self = Self::default();
+        }
+    }
+}
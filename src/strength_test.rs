@@ -0,0 +1,55 @@
+use color_eyre::eyre::{Result, bail};
+use rand::{SeedableRng, rngs::StdRng};
+
+use crate::game::{Mark, OuterBoard, searcher};
+
+const SEEDS: std::ops::Range<u64> = 0..4;
+const OPENING_PLIES: usize = 6;
+const DEEP: usize = 3;
+const SHALLOW: usize = 1;
+
+fn opening(seed: u64) -> (OuterBoard, Mark) {
+    OuterBoard::random_playout(&mut StdRng::seed_from_u64(seed), OPENING_PLIES)
+}
+
+fn play(mut board: OuterBoard, mut to_move: Mark, deep_mark: Mark) -> Option<Mark> {
+    while !board.is_over() {
+        let depth = if to_move == deep_mark { DEEP } else { SHALLOW };
+        let (r#move, _) = searcher::Searcher::search_deterministic(
+            &board,
+            to_move,
+            depth,
+            searcher::SearchOptions::default(),
+        )
+        .0?;
+        board = board.make_move(r#move)?;
+        to_move = !to_move;
+    }
+    board.overall_winner
+}
+
+pub fn run() -> Result<()> {
+    let mut score = 0i32;
+
+    for seed in SEEDS {
+        let (board, to_move) = opening(seed);
+        for deep_mark in [Mark::X, Mark::O] {
+            // the shallow engine would otherwise reuse entries from the deep one
+            searcher::clear_transposition_table();
+
+            let result = match play(board, to_move, deep_mark) {
+                Some(winner) if winner == deep_mark => 1,
+                Some(_) => -1,
+                None => 0,
+            };
+            println!("seed {seed}, depth {DEEP} as {deep_mark}: {result:+}");
+            score += result;
+        }
+    }
+
+    println!("depth {DEEP} vs depth {SHALLOW}: {score:+}");
+    if score < 0 {
+        bail!("depth {DEEP} scored {score} against depth {SHALLOW}");
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use eframe::egui;
+
+use crate::{
+    GridHelper, draw_grid_item, draw_grid_lines, draw_obscuring_square,
+    game::{Mark, OuterBoard},
+    settings::BoardStyle,
+};
+
+const BOARD_SIZE: f32 = 240.0;
+
+// (outer, inner, mark) for one mark on a demo board
+type DemoMark = ((u8, u8), (u8, u8), Mark);
+
+struct Step {
+    title: &'static str,
+    text: &'static str,
+    marks: &'static [DemoMark],
+    active_square: Option<(u8, u8)>,
+}
+
+const STEPS: &[Step] = &[
+    Step {
+        title: "Il tabellone",
+        text: "Supertris è un tris fatto di nove tris. Ogni mossa mette il tuo segno in una \
+               casella di una delle nove sottotabelle.",
+        marks: &[],
+        active_square: None,
+    },
+    Step {
+        title: "Dove si gioca",
+        text: "La casella in cui giochi decide dove gioca l'avversario: qui X ha giocato \
+               nell'angolo in basso a destra, quindi O deve giocare nella sottotabella in \
+               basso a destra, evidenziata.",
+        marks: &[((0, 0), (2, 2), Mark::X)],
+        active_square: Some((2, 2)),
+    },
+    Step {
+        title: "Conquistare una sottotabella",
+        text: "Chi fa tris dentro una sottotabella la conquista, e da lì in poi non ci si \
+               gioca più.",
+        marks: &[
+            ((1, 1), (0, 0), Mark::X),
+            ((1, 1), (1, 1), Mark::X),
+            ((1, 1), (2, 2), Mark::X),
+            ((1, 1), (0, 2), Mark::O),
+            ((1, 1), (2, 0), Mark::O),
+        ],
+        active_square: Some((0, 2)),
+    },
+    Step {
+        title: "Scelta libera",
+        text: "Se la casella ti manda in una sottotabella già chiusa, puoi giocare in \
+               qualunque sottotabella ancora aperta: tutto il tabellone è evidenziato.",
+        marks: &[
+            ((1, 1), (0, 0), Mark::X),
+            ((1, 1), (1, 1), Mark::X),
+            ((1, 1), (2, 2), Mark::X),
+            ((0, 0), (1, 1), Mark::O),
+        ],
+        active_square: None,
+    },
+    Step {
+        title: "Vincere la partita",
+        text: "Conquista tre sottotabelle in fila, come in un tris normale, e la partita è \
+               tua. Qui a X manca solo la sottotabella in basso a destra.",
+        marks: &[
+            ((0, 0), (0, 0), Mark::X),
+            ((0, 0), (0, 1), Mark::X),
+            ((0, 0), (0, 2), Mark::X),
+            ((1, 1), (0, 0), Mark::X),
+            ((1, 1), (1, 1), Mark::X),
+            ((1, 1), (2, 2), Mark::X),
+            ((2, 2), (0, 0), Mark::X),
+            ((2, 2), (1, 1), Mark::O),
+        ],
+        active_square: Some((2, 2)),
+    },
+];
+
+impl Step {
+    fn board(&self) -> OuterBoard {
+        let mut board = OuterBoard::default();
+        for &(outer, inner, mark) in self.marks {
+            board.boards[outer.0 as usize][outer.1 as usize].squares[inner.0 as usize]
+                [inner.1 as usize] = Some(mark);
+        }
+        board.recompute_winners();
+        board.active_square = self.active_square;
+        board
+    }
+}
+
+fn draw_demo_board(ui: &mut egui::Ui, style: &BoardStyle, board: &OuterBoard) {
+    let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(BOARD_SIZE), egui::Sense::hover());
+    let gh = GridHelper::new(rect);
+    draw_grid_lines(ui, style, gh, board.active_square.is_none());
+    for row in 0..3 {
+        for col in 0..3 {
+            let inner_board = &board.boards[row as usize][col as usize];
+            let sub_gh = gh.subgrid(row, col);
+            draw_grid_lines(ui, style, sub_gh, board.active_square == Some((row, col)));
+            for inner_row in 0..3 {
+                for inner_col in 0..3 {
+                    let square = inner_board.squares[inner_row as usize][inner_col as usize];
+                    draw_grid_item(ui, style, sub_gh, inner_row, inner_col, square, false);
+                }
+            }
+            if let Some(winner) = inner_board.winner {
+                draw_obscuring_square(ui, sub_gh);
+                draw_grid_item(ui, style, gh, row, col, Some(winner), false);
+            }
+        }
+    }
+}
+
+// shows step `step` and returns which one to show next, or `None` once it's closed
+pub fn show(ctx: &egui::Context, style: &BoardStyle, step: usize) -> Option<usize> {
+    let current = &STEPS[step.min(STEPS.len() - 1)];
+    let mut next = Some(step);
+    let modal = egui::Modal::new("tutorial_modal".into()).show(ctx, |ui| {
+        ui.set_max_width(BOARD_SIZE * 1.5);
+        ui.heading(format!("{} ({}/{})", current.title, step + 1, STEPS.len()));
+        ui.label(current.text);
+        ui.vertical_centered(|ui| draw_demo_board(ui, style, &current.board()));
+        ui.separator();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(step > 0, egui::Button::new("Indietro"))
+                .clicked()
+            {
+                next = Some(step - 1);
+            }
+            if step + 1 < STEPS.len() {
+                if ui.button("Avanti").clicked() {
+                    next = Some(step + 1);
+                }
+                if ui.button("Salta").clicked() {
+                    next = None;
+                }
+            } else if ui.button("Inizia a giocare").clicked() {
+                next = None;
+            }
+        });
+    });
+    if modal.should_close() {
+        next = None;
+    }
+    next
+}